@@ -8,6 +8,12 @@
 {
   # https://devenv.sh/basics/
   env.GREET = "devenv";
+  # `#[sqlx::test]`s create a scratch database per test through this connection. Queries are
+  # still checked against the `.sqlx` files, so they build without a migrated database.
+  env.DATABASE_URL = "postgres://rssflow@127.0.0.1:5432/rssflow";
+  env.SQLX_OFFLINE = "true";
+  # Used by the Seen node's tests.
+  env.REDIS_URL = "redis://127.0.0.1:6379";
 
   cachix.enable = true;
   cachix.pull = [ "m00nwtchr" ];
@@ -30,6 +36,9 @@
   # https://devenv.sh/services/
   services.postgres = {
     enable = true;
+    listen_addresses = "127.0.0.1";
+    # Needed by `#[sqlx::test]`
+    initialScript = "ALTER ROLE rssflow CREATEDB;";
     initialDatabases = [
       {
         name = "rssflow";
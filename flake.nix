@@ -116,9 +116,11 @@
         rssflow-websub = mkPackage craneLib "websub";
         rssflow-fetch = mkPackage craneLib "fetch";
         rssflow-filter = mkPackage craneLib "filter";
+        rssflow-merge = mkPackage craneLib "merge";
         rssflow-replace = mkPackage craneLib "replace";
         rssflow-retrieve = mkPackage craneLib "retrieve";
         rssflow-sanitize = mkPackage craneLib "sanitize";
+        rssflow-seen = mkPackage craneLib "seen";
       };
 
       packages = mkPackages craneLib;
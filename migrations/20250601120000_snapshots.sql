@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS snapshots
+(
+    flow       TEXT        NOT NULL REFERENCES flows (name) ON DELETE CASCADE,
+    output     TEXT        NOT NULL,
+    payload    BYTEA,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (flow, output)
+);
@@ -0,0 +1,15 @@
+-- Not referencing flows, so a flow's history outlives deleting it.
+CREATE TABLE IF NOT EXISTS flow_revisions
+(
+    flow       TEXT        NOT NULL,
+    revision   INTEGER     NOT NULL,
+    content    JSONB       NOT NULL,
+    author     TEXT,
+    message    TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (flow, revision)
+);
+
+INSERT INTO flow_revisions (flow, revision, content, message)
+SELECT name, 1, content, 'Initial revision'
+FROM flows;
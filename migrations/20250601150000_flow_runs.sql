@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS flow_runs
+(
+    id          BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    flow        TEXT        NOT NULL REFERENCES flows (name) ON DELETE CASCADE,
+    trigger     TEXT        NOT NULL,
+    status      TEXT        NOT NULL,
+    error       TEXT,
+    started_at  TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    nodes       JSONB       NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS flow_runs_flow ON flow_runs (flow, id DESC);
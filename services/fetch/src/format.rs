@@ -0,0 +1,345 @@
+use anyhow::anyhow;
+use encoding_rs::{Encoding, UTF_8};
+use rssflow_service::proto::feed::{Feed, json::JsonFeed};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+	Atom,
+	/// RSS 2.0 and older, including RSS 1.0 (RDF).
+	Rss,
+	JsonFeed,
+}
+
+impl Format {
+	fn from_content_type(content_type: &str) -> Option<Self> {
+		let media_type = content_type.split(';').next()?.trim();
+		match media_type {
+			"application/atom+xml" => Some(Format::Atom),
+			"application/rss+xml" | "application/rdf+xml" => Some(Format::Rss),
+			"application/feed+json" | "application/json" => Some(Format::JsonFeed),
+			_ => None,
+		}
+	}
+
+	/// Guesses the format from the first non-whitespace character and the XML root element.
+	fn sniff(body: &[u8]) -> Option<Self> {
+		let body = body.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(body);
+		let mut rest = body.trim_ascii_start();
+
+		if rest.starts_with(b"{") {
+			return Some(Format::JsonFeed);
+		}
+
+		while let Some(start) = rest.iter().position(|&b| b == b'<') {
+			rest = &rest[start + 1..];
+			match rest.first() {
+				// Declarations, processing instructions and comments
+				Some(b'?' | b'!') => {
+					let end = if rest.starts_with(b"!--") {
+						rest.windows(3).position(|w| w == b"-->")?
+					} else {
+						rest.iter().position(|&b| b == b'>')?
+					};
+					rest = &rest[end..];
+				}
+				Some(_) => {
+					let end = rest
+						.iter()
+						.position(|b| b.is_ascii_whitespace() || matches!(b, b'>' | b'/'))?;
+					let name = &rest[..end];
+					let local = name.rsplit(|&b| b == b':').next()?;
+
+					return match local {
+						b"feed" => Some(Format::Atom),
+						b"rss" | b"RDF" => Some(Format::Rss),
+						_ => None,
+					};
+				}
+				None => return None,
+			}
+		}
+
+		None
+	}
+
+	pub fn detect(content_type: Option<&str>, body: &[u8]) -> Option<Self> {
+		Self::sniff(body).or_else(|| content_type.and_then(Self::from_content_type))
+	}
+}
+
+/// Decodes `body` using the charset of its content type, UTF-8 if there is none.
+pub fn decode(content_type: Option<&str>, body: &[u8]) -> String {
+	let encoding = content_type
+		.and_then(|c| {
+			c.split(';')
+				.skip(1)
+				.filter_map(|param| param.split_once('='))
+				.find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+		})
+		.and_then(|(_, charset)| Encoding::for_label(charset.trim().trim_matches('"').as_bytes()))
+		.unwrap_or(UTF_8);
+
+	encoding.decode(body).0.into_owned()
+}
+
+pub fn parse(content_type: Option<&str>, body: &[u8]) -> anyhow::Result<Feed> {
+	let format = Format::detect(content_type, body).ok_or_else(|| {
+		anyhow!(
+			"unrecognized feed format (content type: {})",
+			content_type.unwrap_or("none")
+		)
+	})?;
+
+	Ok(match format {
+		Format::Atom => (&atom_syndication::Feed::read_from(body)?).into(),
+		Format::Rss => (&rss::Channel::read_from(body)?).into(),
+		Format::JsonFeed => serde_json::from_slice::<JsonFeed>(body)?.into(),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::DateTime;
+	use rssflow_service::proto::feed::{Feed, json::JsonFeed};
+
+	use super::{Format, decode, parse};
+
+	const ATOM: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<!-- <rss> in a comment -->
+<feed xmlns="http://www.w3.org/2005/Atom">
+	<title>Example</title>
+	<id>urn:example</id>
+	<updated>2003-12-13T18:30:02Z</updated>
+</feed>"#;
+
+	const RSS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<rss version="2.0" xmlns:dc="http://purl.org/dc/elements/1.1/">
+	<channel>
+		<title>Example</title>
+		<link>http://example.org/</link>
+		<description>News</description>
+		<lastBuildDate>Sat, 07 Sep 2002 09:42:31 GMT</lastBuildDate>
+		<category domain="http://example.org/categories">tech</category>
+		<item>
+			<title>First</title>
+			<link>http://example.org/1</link>
+			<guid isPermaLink="false">urn:example:1</guid>
+			<pubDate>Sat, 07 Sep 2002 00:00:01 GMT</pubDate>
+			<author>jane@example.org (Jane Doe)</author>
+			<dc:creator>John Roe</dc:creator>
+			<category>news</category>
+			<enclosure url="http://example.org/1.mp3" length="1234" type="audio/mpeg"/>
+		</item>
+		<item>
+			<title>Second</title>
+			<link>http://example.org/2</link>
+		</item>
+	</channel>
+</rss>"#;
+
+	const RDF: &str = r#"<?xml version="1.0"?>
+<rdf:RDF
+	xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+	xmlns:dc="http://purl.org/dc/elements/1.1/"
+	xmlns="http://purl.org/rss/1.0/">
+	<channel rdf:about="http://example.org/rss">
+		<title>Example</title>
+		<link>http://example.org/</link>
+		<description>News</description>
+		<dc:date>2002-09-07T09:42:31Z</dc:date>
+	</channel>
+	<item rdf:about="http://example.org/1">
+		<title>First</title>
+		<link>http://example.org/1</link>
+		<dc:date>2002-09-07T00:00:01+00:00</dc:date>
+		<dc:creator>Jane Doe</dc:creator>
+	</item>
+</rdf:RDF>"#;
+
+	const JSON: &str = r#"{
+		"version": "https://jsonfeed.org/version/1.1",
+		"title": "Example",
+		"home_page_url": "http://example.org/",
+		"feed_url": "http://example.org/feed.json",
+		"items": [
+			{
+				"id": 1,
+				"url": "http://example.org/1",
+				"title": "First",
+				"content_html": "<p>Hi</p>",
+				"date_published": "2002-09-07T00:00:01Z",
+				"date_modified": "2002-09-07T09:42:31Z",
+				"tags": ["news"],
+				"attachments": [
+					{ "url": "http://example.org/1.mp3", "mime_type": "audio/mpeg", "size_in_bytes": 1234 }
+				]
+			}
+		]
+	}"#;
+
+	fn seconds(rfc3339: &str) -> i64 {
+		DateTime::parse_from_rfc3339(rfc3339).unwrap().timestamp()
+	}
+
+	#[test]
+	fn sniff() {
+		assert_eq!(Format::sniff(ATOM.as_bytes()), Some(Format::Atom));
+		assert_eq!(Format::sniff(RSS.as_bytes()), Some(Format::Rss));
+		assert_eq!(Format::sniff(RDF.as_bytes()), Some(Format::Rss));
+		assert_eq!(Format::sniff(JSON.as_bytes()), Some(Format::JsonFeed));
+		assert_eq!(
+			Format::sniff(b"\xEF\xBB\xBF  <feed xmlns=\"http://www.w3.org/2005/Atom\"/>"),
+			Some(Format::Atom)
+		);
+		assert_eq!(Format::sniff(b"<html><body/></html>"), None);
+		assert_eq!(Format::sniff(b"<!-- unterminated"), None);
+	}
+
+	#[test]
+	fn detect_falls_back_to_content_type() {
+		assert_eq!(
+			Format::detect(Some("application/rss+xml; charset=utf-8"), b"  "),
+			Some(Format::Rss)
+		);
+		assert_eq!(
+			Format::detect(Some("application/rss+xml"), ATOM.as_bytes()),
+			Some(Format::Atom)
+		);
+		assert_eq!(Format::detect(Some("text/html"), b""), None);
+		assert!(parse(Some("text/html"), b"<html/>").is_err());
+	}
+
+	#[test]
+	fn rss() {
+		let feed = parse(None, RSS.as_bytes()).unwrap();
+		assert_eq!(feed.title, "Example");
+		assert_eq!(feed.id, "http://example.org/");
+		assert_eq!(
+			feed.updated.unwrap().seconds,
+			seconds("2002-09-07T09:42:31Z")
+		);
+		assert_eq!(feed.categories[0].term, "tech");
+		assert_eq!(feed.categories[0].scheme, "http://example.org/categories");
+
+		let entry = &feed.entries[0];
+		assert_eq!(entry.id, "urn:example:1");
+		assert_eq!(
+			entry.published.unwrap().seconds,
+			seconds("2002-09-07T00:00:01Z")
+		);
+		assert_eq!(entry.authors[0].email, "jane@example.org");
+		assert_eq!(entry.authors[0].name, "Jane Doe");
+		assert_eq!(entry.authors[1].name, "John Roe");
+		assert_eq!(entry.categories[0].term, "news");
+
+		let enclosure = entry.links.iter().find(|l| l.rel == "enclosure").unwrap();
+		assert_eq!(enclosure.href, "http://example.org/1.mp3");
+		assert_eq!(enclosure.media_type, "audio/mpeg");
+		assert_eq!(enclosure.length, "1234");
+
+		// Without a guid, the link identifies the item.
+		assert_eq!(feed.entries[1].id, "http://example.org/2");
+
+		let channel = rss::Channel::from(feed);
+		assert_eq!(
+			channel.last_build_date.as_deref(),
+			Some("Sat, 7 Sep 2002 09:42:31 +0000")
+		);
+		let item = &channel.items[0];
+		let guid = item.guid.as_ref().unwrap();
+		assert_eq!(guid.value, "urn:example:1");
+		assert!(!guid.permalink);
+		assert_eq!(
+			item.pub_date.as_deref(),
+			Some("Sat, 7 Sep 2002 00:00:01 +0000")
+		);
+		assert_eq!(item.categories[0].name, "news");
+		let enclosure = item.enclosure.as_ref().unwrap();
+		assert_eq!(enclosure.url, "http://example.org/1.mp3");
+		assert_eq!(enclosure.length, "1234");
+		assert_eq!(enclosure.mime_type, "audio/mpeg");
+		assert!(channel.items[1].guid.as_ref().unwrap().permalink);
+	}
+
+	#[test]
+	fn rdf() {
+		let feed = parse(Some("application/rdf+xml"), RDF.as_bytes()).unwrap();
+		assert_eq!(feed.title, "Example");
+		assert_eq!(
+			feed.updated.unwrap().seconds,
+			seconds("2002-09-07T09:42:31Z")
+		);
+
+		let entry = &feed.entries[0];
+		assert_eq!(entry.id, "http://example.org/1");
+		assert_eq!(
+			entry.published.unwrap().seconds,
+			seconds("2002-09-07T00:00:01Z")
+		);
+		assert_eq!(entry.authors[0].name, "Jane Doe");
+	}
+
+	#[test]
+	fn json_feed() {
+		let feed = parse(Some("application/feed+json"), JSON.as_bytes()).unwrap();
+		assert_eq!(feed.id, "http://example.org/feed.json");
+		assert_eq!(
+			feed.updated.unwrap().seconds,
+			seconds("2002-09-07T09:42:31Z")
+		);
+
+		let entry = &feed.entries[0];
+		assert_eq!(entry.id, "1");
+		assert_eq!(
+			entry.published.unwrap().seconds,
+			seconds("2002-09-07T00:00:01Z")
+		);
+		assert_eq!(
+			entry.updated.unwrap().seconds,
+			seconds("2002-09-07T09:42:31Z")
+		);
+		assert_eq!(entry.categories[0].term, "news");
+		let enclosure = entry.links.iter().find(|l| l.rel == "enclosure").unwrap();
+		assert_eq!(enclosure.href, "http://example.org/1.mp3");
+		assert_eq!(enclosure.length, "1234");
+
+		let json = JsonFeed::from(feed);
+		assert_eq!(
+			json.feed_url.as_deref(),
+			Some("http://example.org/feed.json")
+		);
+		let item = &json.items[0];
+		assert_eq!(item.id, "1");
+		assert_eq!(
+			item.date_published.as_deref(),
+			Some("2002-09-07T00:00:01+00:00")
+		);
+		assert_eq!(item.tags, ["news"]);
+		assert_eq!(item.attachments[0].size_in_bytes, Some(1234));
+		assert_eq!(item.attachments[0].mime_type, "audio/mpeg");
+	}
+
+	#[test]
+	fn decode_charset() {
+		let body = b"<title>Caf\xE9</title>";
+		assert_eq!(
+			decode(Some("application/rss+xml; charset=ISO-8859-1"), body),
+			"<title>Café</title>"
+		);
+		assert_eq!(
+			decode(Some("application/rss+xml;charset=\"windows-1252\""), body),
+			"<title>Café</title>"
+		);
+		assert_eq!(
+			decode(Some("application/atom+xml"), "Café".as_bytes()),
+			"Café"
+		);
+		assert_eq!(decode(None, "Café".as_bytes()), "Café");
+	}
+
+	#[test]
+	fn empty_feed() {
+		let feed: Feed = parse(None, br#"{"version": "", "title": "", "items": []}"#).unwrap();
+		assert!(feed.entries.is_empty());
+	}
+}
@@ -3,6 +3,7 @@
 use rssflow_service::{ServiceExt, proto, proto::node::node_service_server::NodeServiceServer};
 use runesys::{Service, config::config};
 
+mod format;
 mod service;
 
 #[derive(Service)]
@@ -11,6 +12,7 @@ mod service;
 #[fd_set(proto::FILE_DESCRIPTOR_SET)]
 struct FetchNode {
 	conn: redis::aio::MultiplexedConnection,
+	client: reqwest::Client,
 }
 
 #[tokio::main]
@@ -20,7 +22,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 	let redis = redis::Client::open(config.redis_url.as_str())?;
 	let conn = redis.get_multiplexed_async_connection().await?;
-	let node = FetchNode { conn };
+	let node = FetchNode {
+		conn,
+		client: reqwest::Client::new(),
+	};
 
-	Ok(node.builder().run().await?)
+	Ok(node.builder().with_reporter().run().await?)
 }
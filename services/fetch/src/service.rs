@@ -1,27 +1,304 @@
 use std::{str::FromStr, time::Duration};
 
-use atom_syndication::Feed;
+use chrono::{DateTime, Utc};
 use redis::AsyncCommands;
-use reqwest::{header, header::LINK};
+use reqwest::{
+	StatusCode, header,
+	header::{HeaderMap, LINK},
+};
 use rssflow_service::{
-	ServiceExt2, check_node, interceptor,
+	ServiceExt2, X_WEBSUB,
+	channel::channels,
+	check_node,
+	config::config,
+	interceptor, payload_type,
 	proto::{
+		feed::Feed,
 		node::{
-			NodeMeta, PingRequest, PingResponse, ProcessRequest, ProcessResponse,
-			node_service_server::NodeService,
+			DescribeRequest, NodeDescription, NodeMeta, OptionSchema, OptionType, PingRequest,
+			PingResponse, ProcessRequest, ProcessResponse, node_service_server::NodeService,
 		},
 		websub::{
-			SubscribeRequest, WebSub, WebSubEvent, web_sub_service_client::WebSubServiceClient,
+			SignaturePolicy, SubscribeRequest, WebSub, WebSubEvent,
+			web_sub_service_client::WebSubServiceClient,
 		},
 	},
 	try_from_request,
 };
 use runesys::{Service, cache::Cached, telemetry::propagation::send_trace};
-use tonic::{Request, Response, Status, transport::Endpoint};
-use tracing::{error, info, instrument};
+use serde::{Deserialize, Serialize};
+use tonic::{Request, Response, Status, metadata::MetadataValue};
+use tracing::{error, info, instrument, warn};
 use url::Url;
 
-use crate::FetchNode;
+use crate::{FetchNode, format};
+
+const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60); // 1h
+/// Seconds until the origin expects to be asked again, when it is throttling.
+const RETRY_AFTER: &str = "retry-after";
+
+/// Upstream response, cached as-is and parsed again on cache hits.
+#[derive(Serialize, Deserialize, Clone)]
+struct Document {
+	content_type: Option<String>,
+	body: String,
+	#[serde(default)]
+	etag: Option<String>,
+	#[serde(default)]
+	last_modified: Option<String>,
+	/// Freshness lifetime requested by the origin, in seconds.
+	#[serde(default)]
+	max_age: Option<u64>,
+	/// Whether the feed was subscribed to when it was fetched.
+	#[serde(default)]
+	websub: Option<WebSubState>,
+}
+
+/// The `websub` option.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum WebSubMode {
+	/// Subscribe when the feed advertises a hub.
+	Auto,
+	Off,
+	/// Fail unless the feed could be subscribed to.
+	Required,
+}
+
+impl FromStr for WebSubMode {
+	type Err = Status;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"auto" => Ok(WebSubMode::Auto),
+			"off" => Ok(WebSubMode::Off),
+			"required" => Ok(WebSubMode::Required),
+			_ => Err(Status::invalid_argument(format!(
+				"invalid websub mode: {s}"
+			))),
+		}
+	}
+}
+
+/// Reported in the `x-websub` response metadata, so flows know whether a feed is push-driven.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum WebSubState {
+	Off,
+	/// The feed doesn't advertise a hub.
+	Unavailable,
+	Subscribed,
+	Failed,
+}
+
+impl WebSubState {
+	fn as_str(self) -> &'static str {
+		match self {
+			WebSubState::Off => "off",
+			WebSubState::Unavailable => "unavailable",
+			WebSubState::Subscribed => "subscribed",
+			WebSubState::Failed => "failed",
+		}
+	}
+}
+
+/// Responds with the feed, failing if WebSub was `required` but the feed isn't subscribed to.
+fn respond(
+	feed: Feed,
+	mode: WebSubMode,
+	state: WebSubState,
+) -> Result<Response<ProcessResponse>, Status> {
+	if mode == WebSubMode::Required && state != WebSubState::Subscribed {
+		return Err(Status::failed_precondition(format!(
+			"WebSub is required, but the feed is {}",
+			state.as_str()
+		)));
+	}
+
+	let mut response = Response::new(ProcessResponse {
+		payload: Some(feed.into()),
+	});
+	response
+		.metadata_mut()
+		.insert(X_WEBSUB, MetadataValue::from_static(state.as_str()));
+	Ok(response)
+}
+
+impl Document {
+	fn parse(&self) -> Result<Feed, Status> {
+		format::parse(self.content_type.as_deref(), self.body.as_bytes())
+			.map_err(|e| Status::internal(e.to_string()))
+	}
+}
+
+fn header_string(headers: &HeaderMap, name: header::HeaderName) -> Option<String> {
+	headers
+		.get(name)
+		.and_then(|v| v.to_str().ok())
+		.map(ToString::to_string)
+}
+
+/// `max-age` directive of the `Cache-Control` header, `no-cache` and `no-store` count as 0.
+fn max_age(headers: &HeaderMap) -> Option<u64> {
+	let cache_control = headers.get(header::CACHE_CONTROL)?.to_str().ok()?;
+	cache_control.split(',').find_map(|directive| {
+		let directive = directive.trim();
+		if directive.eq_ignore_ascii_case("no-cache") || directive.eq_ignore_ascii_case("no-store")
+		{
+			Some(0)
+		} else {
+			directive
+				.strip_prefix("max-age=")?
+				.trim_matches('"')
+				.parse()
+				.ok()
+		}
+	})
+}
+
+/// `Retry-After` header, either in delay-seconds or as an HTTP-date.
+fn retry_after(headers: &HeaderMap) -> Option<u64> {
+	let value = headers.get(header::RETRY_AFTER)?.to_str().ok()?.trim();
+	value.parse().ok().or_else(|| {
+		let date = DateTime::parse_from_rfc2822(value).ok()?;
+		u64::try_from((date.with_timezone(&Utc) - Utc::now()).num_seconds()).ok()
+	})
+}
+
+/// Requests the feed from the origin, revalidating the `cached` document if there is one.
+async fn refresh(
+	client: &reqwest::Client,
+	url: &Url,
+	cached: Option<Document>,
+) -> Result<(Document, Option<WebSub>), Status> {
+	let mut request = client.get(url.clone());
+	if let Some(cached) = &cached {
+		if let Some(etag) = &cached.etag {
+			request = request.header(header::IF_NONE_MATCH, etag);
+		}
+		if let Some(last_modified) = &cached.last_modified {
+			request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+		}
+	}
+
+	let response = request
+		.send()
+		.await
+		.map_err(|e| Status::unavailable(format!("Request to {} failed: {e}", e.url().unwrap())))?;
+	let headers = response.headers();
+
+	match (response.status(), cached) {
+		(StatusCode::NOT_MODIFIED, Some(mut cached)) => {
+			info!("Not modified");
+			cached.max_age = max_age(headers).or(cached.max_age);
+			return Ok((cached, None));
+		}
+		(StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE, Some(mut cached)) => {
+			warn!("{url} responded with {}, serving stale", response.status());
+			cached.max_age = retry_after(headers).or(cached.max_age);
+			return Ok((cached, None));
+		}
+		(StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE, None) => {
+			// Nothing to serve yet, tell the caller when to come back.
+			let Some(delay) = retry_after(headers) else {
+				return Err(Status::unavailable(format!(
+					"{url} responded with {}",
+					response.status()
+				)));
+			};
+			let mut status = Status::unavailable(format!(
+				"{url} responded with {}, retry after {delay}s",
+				response.status()
+			));
+			status
+				.metadata_mut()
+				.insert(RETRY_AFTER, MetadataValue::from(delay));
+			return Err(status);
+		}
+		_ => {}
+	}
+
+	let response = response
+		.error_for_status()
+		.map_err(|e| Status::unavailable(e.to_string()))?;
+	let headers = response.headers();
+
+	let websub = headers
+		.get(LINK)
+		.and_then(|v| v.to_str().ok())
+		.and_then(|v| WebSub::from_str(v).ok());
+
+	let content_type = header_string(headers, header::CONTENT_TYPE);
+	let etag = header_string(headers, header::ETAG);
+	let last_modified = header_string(headers, header::LAST_MODIFIED);
+	let max_age = max_age(headers);
+
+	let document = Document {
+		content_type,
+		body: response
+			.text()
+			.await
+			.map_err(|e| Status::internal(e.to_string()))?,
+		etag,
+		last_modified,
+		max_age,
+		websub: None,
+	};
+
+	Ok((document, websub))
+}
+
+/// Which pushes the subscription accepts, from the `websub_signature` and `websub_algorithms`
+/// options. Without either, the WebSub service's defaults apply.
+fn signature_policy(request: &ProcessRequest) -> Result<Option<SignaturePolicy>, Status> {
+	let require_signature = request
+		.get_option::<&bool>("websub_signature")
+		.transpose()?
+		.copied();
+	let algorithms = request
+		.get_option::<&String>("websub_algorithms")
+		.transpose()?;
+	if require_signature.is_none() && algorithms.is_none() {
+		return Ok(None);
+	}
+
+	Ok(Some(SignaturePolicy {
+		require_signature: require_signature.unwrap_or(true),
+		algorithms: algorithms
+			.into_iter()
+			.flat_map(|a| a.split(','))
+			.map(str::trim)
+			.filter(|a| !a.is_empty())
+			.map(str::to_string)
+			.collect(),
+	}))
+}
+
+impl FetchNode {
+	/// Subscribes the flow to the feed at `url` through the WebSub service.
+	async fn subscribe(
+		&self,
+		websub: WebSub,
+		flow: String,
+		url: &Url,
+		policy: Option<SignaturePolicy>,
+	) -> Result<(), Status> {
+		let websub_url = &config::<Self>().websub_url;
+		let channel = channels()
+			.get(websub_url.as_str())
+			.map_err(|e| Status::internal(format!("{e:#}")))?;
+
+		WebSubServiceClient::with_interceptor(channel, interceptor(send_trace))
+			.subscribe(SubscribeRequest {
+				sub: Some(websub),
+				node: Some(Self::node_meta()),
+				flow,
+				policy,
+				url: url.to_string(),
+			})
+			.await?;
+		Ok(())
+	}
+}
 
 #[tonic::async_trait]
 impl NodeService for FetchNode {
@@ -39,47 +316,50 @@ impl NodeService for FetchNode {
 			Url::from_str(s).map_err(|e| Status::invalid_argument(e.to_string()))
 		})?;
 
-		let feed = if let Ok(wse) = try_from_request::<WebSubEvent>(&request) {
-			Feed::read_from(&wse.body[..]).map_err(|e| Status::internal(e.to_string()))?
+		let mode = match request.get_option::<&String>("websub") {
+			Some(mode) => mode?.parse()?,
+			None => WebSubMode::Auto,
+		};
+		let policy = signature_policy(&request)?;
+
+		let (mut document, feed) = if let Ok(wse) = try_from_request::<WebSubEvent>(&request) {
+			let content_type = Some(wse.content_type).filter(|c| !c.is_empty());
+			let document = Document {
+				body: format::decode(content_type.as_deref(), &wse.body),
+				content_type,
+				etag: None,
+				last_modified: None,
+				max_age: None,
+				websub: Some(WebSubState::Subscribed),
+			};
+			let feed = document.parse()?;
+			(document, feed)
 		} else {
-			let ttl = Duration::from_secs(match request.get_option::<&f64>("ttl") {
-				Some(r) => r.map(|n| *n as u64)?,
-				None => 60 * 60, // 1h
-			});
+			// Without an explicit ttl, the origin's caching headers decide.
+			let ttl = match request.get_option::<&f64>("ttl") {
+				Some(r) => Some(r.map(|n| Duration::from_secs(*n as u64))?),
+				None => None,
+			};
 
-			let cached: Option<Cached<Feed>> = conn.get(format!("cache:{url}")).await.ok();
-			if let Some(cached) = cached {
+			let cached: Option<Cached<Document>> = conn.get(format!("cache:{url}")).await.ok();
+			if let Some(cached) = &cached {
+				let ttl = ttl
+					.or(cached.value.max_age.map(Duration::from_secs))
+					.unwrap_or(DEFAULT_TTL);
 				if cached.elapsed() <= ttl {
 					info!("Cache hit");
-					let feed: rssflow_service::proto::feed::Feed = (&cached.value).into();
-					return Ok(Response::new(ProcessResponse {
-						payload: Some(feed.into()),
-					}));
-				}
-			}
-
-			let response = reqwest::get(url.clone()).await.map_err(|e| {
-				Status::unavailable(format!("Request to {} failed: {e}", e.url().unwrap()))
-			})?;
-
-			if let Some(ct) = response.headers().get(header::CONTENT_TYPE) {
-				if ct.eq("application/rss+xml") {
-					// TODO: Handle RSS channels (upgrade to atom)
+					let feed = cached.value.parse()?;
+					let state = match mode {
+						WebSubMode::Off => WebSubState::Off,
+						_ => cached.value.websub.unwrap_or(WebSubState::Unavailable),
+					};
+					return respond(feed, mode, state);
 				}
 			}
 
-			let websub = response
-				.headers()
-				.get(LINK)
-				.and_then(|v| v.to_str().ok())
-				.and_then(|v| WebSub::from_str(v).ok());
-
-			let content = response
-				.bytes()
-				.await
-				.map_err(|e| Status::internal(e.to_string()))?;
-			let feed =
-				Feed::read_from(&content[..]).map_err(|e| Status::internal(e.to_string()))?;
+			let (mut document, websub) =
+				refresh(&self.client, &url, cached.map(|c| c.value)).await?;
+			let feed = document.parse()?;
 
 			let websub = websub.or_else(|| {
 				let hub = feed.links.iter().find(|l| l.rel.eq("hub"));
@@ -95,48 +375,324 @@ impl NodeService for FetchNode {
 				}
 			});
 
-			if let Some(websub) = websub {
-				info!("{websub:?}");
-
-				let websub_service = "http://[::]:50052";
+			let state = match (mode, websub) {
+				(WebSubMode::Off, _) => WebSubState::Off,
+				(_, None) => WebSubState::Unavailable,
+				(_, Some(websub)) => {
+					info!("Subscribing to `{}` at {}", websub.topic, websub.hub);
+					let flow = request
+						.context
+						.as_ref()
+						.map(|c| c.flow.clone())
+						.unwrap_or_default();
 
-				match async {
-					let endpoint = Endpoint::from_str(websub_service)?;
-					endpoint.connect().await
-				}
-				.await
-				{
-					Ok(channel) => {
-						let mut client =
-							WebSubServiceClient::with_interceptor(channel, interceptor(send_trace));
-
-						let _ = client
-							.subscribe(SubscribeRequest {
-								sub: Some(websub),
-								node: Some(Self::node_meta()),
-							})
-							.await;
+					match self.subscribe(websub, flow, &url, policy).await {
+						Ok(()) => WebSubState::Subscribed,
+						Err(status) => {
+							error!("Subscribing failed: {status}");
+							WebSubState::Failed
+						}
 					}
-					Err(err) => error!("{err}"),
 				}
-			}
+			};
+			document.websub = Some(state);
 
-			feed
+			(document, feed)
 		};
 
-		let cached = Cached::new(feed.clone());
+		let state = document.websub.unwrap_or(WebSubState::Unavailable);
+		let cached = Cached::new(document);
 		let _: () = conn
 			.set_ex(format!("cache:{url}"), cached, 86400)
 			.await
 			.map_err(|e| Status::internal(e.to_string()))?;
 
-		let feed: rssflow_service::proto::feed::Feed = (&feed).into();
-		Ok(Response::new(ProcessResponse {
-			payload: Some(feed.into()),
-		}))
+		respond(feed, mode, state)
 	}
 
 	async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
 		Self::respond_to_ping()
 	}
+
+	async fn describe(
+		&self,
+		request: Request<DescribeRequest>,
+	) -> Result<Response<NodeDescription>, Status> {
+		Self::respond_to_describe(NodeDescription {
+			description:
+				"Fetches an Atom, RSS or JSON Feed, subscribing to it over WebSub when possible"
+					.to_string(),
+			input_types: vec![payload_type::<WebSubEvent>()],
+			output_type: payload_type::<Feed>(),
+			options: vec![
+				OptionSchema::required("url", OptionType::String)
+					.with_description("URL of the feed"),
+				OptionSchema::optional("ttl", OptionType::Number).with_description(
+					"Seconds to cache the feed for, defaults to the origin's max-age or an hour",
+				),
+				OptionSchema::optional("websub", OptionType::String)
+					.with_description(
+						"Whether to subscribe to the feed's WebSub hub, `required` fails without one",
+					)
+					.with_values(&["auto", "off", "required"])
+					.with_default("auto".to_string()),
+				OptionSchema::optional("websub_signature", OptionType::Bool)
+					.with_description("Whether pushes must be signed by the hub")
+					.with_default(true),
+				OptionSchema::optional("websub_algorithms", OptionType::String).with_description(
+					"Comma-separated HMAC algorithms to accept in push signatures, e.g. `sha256,sha512`",
+				),
+			],
+			..NodeDescription::default()
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::{TimeDelta, Utc};
+	use prost_types::Struct;
+	use reqwest::header::{CACHE_CONTROL, HeaderMap, HeaderValue, RETRY_AFTER};
+	use rssflow_service::{
+		X_WEBSUB,
+		proto::{feed::Feed, node::ProcessRequest, websub::SignaturePolicy},
+	};
+	use tokio::{
+		io::{AsyncReadExt, AsyncWriteExt},
+		net::TcpListener,
+	};
+	use tonic::Code;
+	use url::Url;
+
+	use super::{
+		Document, WebSubMode, WebSubState, max_age, refresh, respond, retry_after, signature_policy,
+	};
+
+	fn headers(name: reqwest::header::HeaderName, value: &str) -> HeaderMap {
+		let mut headers = HeaderMap::new();
+		headers.insert(name, HeaderValue::from_str(value).unwrap());
+		headers
+	}
+
+	/// Serves a single request, answering with `respond(request)`.
+	async fn serve(respond: fn(&str) -> String) -> Url {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let url = Url::parse(&format!("http://{}/feed", listener.local_addr().unwrap())).unwrap();
+
+		tokio::spawn(async move {
+			let (mut stream, _) = listener.accept().await.unwrap();
+			let mut request = Vec::new();
+			let mut buf = [0; 1024];
+			while !request.ends_with(b"\r\n\r\n") {
+				let n = stream.read(&mut buf).await.unwrap();
+				if n == 0 {
+					break;
+				}
+				request.extend_from_slice(&buf[..n]);
+			}
+
+			let request = String::from_utf8_lossy(&request).to_ascii_lowercase();
+			let response = respond(&request);
+			stream.write_all(response.as_bytes()).await.unwrap();
+		});
+
+		url
+	}
+
+	fn cached() -> Document {
+		Document {
+			content_type: Some("application/atom+xml".to_string()),
+			body: "<feed/>".to_string(),
+			etag: Some("\"v1\"".to_string()),
+			last_modified: None,
+			max_age: Some(60),
+			websub: None,
+		}
+	}
+
+	#[test]
+	fn websub_mode() {
+		assert_eq!("auto".parse::<WebSubMode>().unwrap(), WebSubMode::Auto);
+		assert_eq!("off".parse::<WebSubMode>().unwrap(), WebSubMode::Off);
+		assert_eq!(
+			"required".parse::<WebSubMode>().unwrap(),
+			WebSubMode::Required
+		);
+
+		let err = "Required".parse::<WebSubMode>().unwrap_err();
+		assert_eq!(err.code(), Code::InvalidArgument);
+	}
+
+	#[test]
+	fn websub_required() {
+		for state in [
+			WebSubState::Off,
+			WebSubState::Unavailable,
+			WebSubState::Failed,
+		] {
+			let err = respond(Feed::default(), WebSubMode::Required, state).unwrap_err();
+			assert_eq!(err.code(), Code::FailedPrecondition);
+			assert!(err.message().contains(state.as_str()));
+		}
+
+		let response = respond(
+			Feed::default(),
+			WebSubMode::Required,
+			WebSubState::Subscribed,
+		)
+		.unwrap();
+		assert_eq!(response.metadata().get(X_WEBSUB).unwrap(), "subscribed");
+	}
+
+	#[test]
+	fn websub_reported() {
+		let response = respond(Feed::default(), WebSubMode::Auto, WebSubState::Failed).unwrap();
+		assert_eq!(response.metadata().get(X_WEBSUB).unwrap(), "failed");
+	}
+
+	#[test]
+	fn websub_policy() {
+		let policy = |options: Vec<(&str, prost_types::Value)>| {
+			let request = ProcessRequest {
+				options: Some(Struct {
+					fields: options
+						.into_iter()
+						.map(|(k, v)| (k.to_string(), v))
+						.collect(),
+				}),
+				..ProcessRequest::default()
+			};
+			signature_policy(&request)
+		};
+
+		assert_eq!(policy(vec![]).unwrap(), None);
+		assert_eq!(
+			policy(vec![("websub_signature", false.into())]).unwrap(),
+			Some(SignaturePolicy {
+				require_signature: false,
+				algorithms: vec![],
+			})
+		);
+		assert_eq!(
+			policy(vec![(
+				"websub_algorithms",
+				"sha256, sha512,".to_string().into()
+			)])
+			.unwrap(),
+			Some(SignaturePolicy {
+				require_signature: true,
+				algorithms: vec!["sha256".to_string(), "sha512".to_string()],
+			})
+		);
+
+		let err = policy(vec![("websub_signature", "yes".to_string().into())]).unwrap_err();
+		assert_eq!(err.code(), Code::InvalidArgument);
+	}
+
+	#[test]
+	fn cache_control() {
+		assert_eq!(max_age(&headers(CACHE_CONTROL, "max-age=60")), Some(60));
+		assert_eq!(
+			max_age(&headers(CACHE_CONTROL, "public, max-age=\"30\"")),
+			Some(30)
+		);
+		assert_eq!(max_age(&headers(CACHE_CONTROL, "no-cache")), Some(0));
+		assert_eq!(
+			max_age(&headers(CACHE_CONTROL, "No-Store, max-age=60")),
+			Some(0)
+		);
+		assert_eq!(max_age(&headers(CACHE_CONTROL, "private")), None);
+		assert_eq!(max_age(&HeaderMap::new()), None);
+	}
+
+	#[test]
+	fn retry_after_forms() {
+		assert_eq!(retry_after(&headers(RETRY_AFTER, "120")), Some(120));
+
+		let date = (Utc::now() + TimeDelta::seconds(60)).to_rfc2822();
+		let delay = retry_after(&headers(RETRY_AFTER, &date)).unwrap();
+		assert!((55..=60).contains(&delay), "{delay}");
+
+		let past = (Utc::now() - TimeDelta::seconds(60)).to_rfc2822();
+		assert_eq!(retry_after(&headers(RETRY_AFTER, &past)), None);
+		assert_eq!(retry_after(&headers(RETRY_AFTER, "soon")), None);
+	}
+
+	#[tokio::test]
+	async fn refresh_stores_caching_headers() {
+		let url = serve(|_| {
+			"HTTP/1.1 200 OK\r\n\
+			content-type: application/atom+xml; charset=utf-8\r\n\
+			etag: \"v1\"\r\n\
+			last-modified: Sat, 07 Sep 2002 09:42:31 GMT\r\n\
+			cache-control: max-age=300\r\n\
+			content-length: 7\r\n\r\n<feed/>"
+				.to_string()
+		})
+		.await;
+
+		let (document, _) = refresh(&reqwest::Client::new(), &url, None).await.unwrap();
+		assert_eq!(document.body, "<feed/>");
+		assert_eq!(document.etag.as_deref(), Some("\"v1\""));
+		assert_eq!(
+			document.last_modified.as_deref(),
+			Some("Sat, 07 Sep 2002 09:42:31 GMT")
+		);
+		assert_eq!(document.max_age, Some(300));
+	}
+
+	#[tokio::test]
+	async fn refresh_revalidates() {
+		let url = serve(|request| {
+			if request.contains("if-none-match: \"v1\"") {
+				"HTTP/1.1 304 Not Modified\r\ncache-control: max-age=600\r\n\r\n".to_string()
+			} else {
+				"HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n".to_string()
+			}
+		})
+		.await;
+
+		let (document, _) = refresh(&reqwest::Client::new(), &url, Some(cached()))
+			.await
+			.unwrap();
+		assert_eq!(document.body, "<feed/>");
+		assert_eq!(document.max_age, Some(600));
+	}
+
+	#[tokio::test]
+	async fn refresh_serves_stale_when_throttled() {
+		let url = serve(|_| {
+			"HTTP/1.1 503 Service Unavailable\r\nretry-after: 120\r\ncontent-length: 0\r\n\r\n"
+				.to_string()
+		})
+		.await;
+
+		let (document, _) = refresh(&reqwest::Client::new(), &url, Some(cached()))
+			.await
+			.unwrap();
+		assert_eq!(document.body, "<feed/>");
+		assert_eq!(document.max_age, Some(120));
+	}
+
+	#[tokio::test]
+	async fn refresh_fails_when_throttled_without_cache() {
+		let url = serve(|_| {
+			"HTTP/1.1 429 Too Many Requests\r\nretry-after: 120\r\ncontent-length: 0\r\n\r\n"
+				.to_string()
+		})
+		.await;
+
+		let Err(status) = refresh(&reqwest::Client::new(), &url, None).await else {
+			panic!("expected an error");
+		};
+		assert_eq!(status.code(), Code::Unavailable);
+		assert!(status.message().contains("retry after 120s"));
+		assert_eq!(
+			status
+				.metadata()
+				.get(super::RETRY_AFTER)
+				.and_then(|v| v.to_str().ok()),
+			Some("120")
+		);
+	}
 }
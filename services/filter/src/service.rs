@@ -2,12 +2,12 @@ use std::str::FromStr;
 
 use regex::Regex;
 use rssflow_service::{
-	ServiceExt2, check_node,
+	ServiceExt2, check_node, payload_type,
 	proto::{
 		feed::Feed,
 		node::{
-			Field, PingRequest, PingResponse, ProcessRequest, ProcessResponse,
-			node_service_server::NodeService,
+			DescribeRequest, NodeDescription, OptionSchema, OptionType, PingRequest, PingResponse,
+			ProcessRequest, ProcessResponse, node_service_server::NodeService,
 		},
 	},
 	try_from_request,
@@ -22,6 +22,23 @@ enum Filter {
 	Contains(String),
 }
 
+/// How the values of multi-valued fields are combined.
+enum Match {
+	/// At least one value matches the filter.
+	Any,
+	/// Every value matches the filter.
+	All,
+}
+
+impl Filter {
+	fn is_match(&self, value: &str) -> bool {
+		match self {
+			Filter::Regex(regex) => regex.is_match(value),
+			Filter::Contains(str) => value.contains(str),
+		}
+	}
+}
+
 #[tonic::async_trait]
 impl NodeService for FilterNode {
 	#[instrument(skip_all)]
@@ -35,10 +52,7 @@ impl NodeService for FilterNode {
 
 		let mut feed: Feed = try_from_request(&request)?;
 
-		let field = request.get_option_required("field").and_then(|f: &f64| {
-			Field::try_from(*f as i32)
-				.map_err(|e| Status::invalid_argument("not a valid field enum value"))
-		})?;
+		let field = request.get_field("field")?;
 
 		let filter = match request.get_option::<&String>("contains") {
 			Some(cr) => cr.map(|s| Filter::Contains(s.clone()))?,
@@ -54,19 +68,31 @@ impl NodeService for FilterNode {
 			},
 		};
 
+		let r#match = match request.get_option::<&String>("match") {
+			Some(r) => match r?.as_str() {
+				"any" => Match::Any,
+				"all" => Match::All,
+				m => Err(Status::invalid_argument(format!(
+					"unknown match {m}: oneof [any, all]"
+				)))?,
+			},
+			None => Match::Any,
+		};
+
 		let invert = match request.get_option::<&bool>("invert") {
 			Some(r) => r.copied()?,
 			None => false,
 		};
 
 		feed.entries.retain(|item| {
-			let cmp = item.value(field);
-			let cmp = if let Some(cmp) = cmp { cmp } else { "" };
+			let values = item.values(field);
 
-			let value = match &filter {
-				Filter::Regex(regex) => regex.is_match(cmp),
-				Filter::Contains(str) => cmp.contains(str),
-			};
+			// Entries without the field never match.
+			let value = !values.is_empty()
+				&& match r#match {
+					Match::Any => values.iter().any(|v| filter.is_match(v)),
+					Match::All => values.iter().all(|v| filter.is_match(v)),
+				};
 
 			if invert { !value } else { value }
 		});
@@ -79,4 +105,33 @@ impl NodeService for FilterNode {
 	async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
 		Self::respond_to_ping()
 	}
+
+	async fn describe(
+		&self,
+		request: Request<DescribeRequest>,
+	) -> Result<Response<NodeDescription>, Status> {
+		Self::respond_to_describe(NodeDescription {
+			description: "Keeps the entries matching a filter".to_string(),
+			input_types: vec![payload_type::<Feed>()],
+			output_type: payload_type::<Feed>(),
+			options: vec![
+				OptionSchema::required("field", OptionType::Field)
+					.with_description("Entry field to match"),
+				OptionSchema::optional("contains", OptionType::String)
+					.with_description("Text the field has to contain"),
+				OptionSchema::optional("regex", OptionType::String)
+					.with_description("Regular expression the field has to match"),
+				OptionSchema::optional("match", OptionType::String)
+					.with_description(
+						"Whether any or all values of multi-valued fields have to match",
+					)
+					.with_values(&["any", "all"])
+					.with_default("any".to_string()),
+				OptionSchema::optional("invert", OptionType::Bool)
+					.with_description("Keep the entries that don't match instead")
+					.with_default(false),
+			],
+			..NodeDescription::default()
+		})
+	}
 }
@@ -0,0 +1,17 @@
+#![warn(clippy::pedantic)]
+
+use rssflow_service::{ServiceExt, proto, proto::node::node_service_server::NodeServiceServer};
+use runesys::Service;
+
+mod service;
+
+#[derive(Service)]
+#[service("Merge")]
+#[server(NodeServiceServer)]
+#[fd_set(proto::FILE_DESCRIPTOR_SET)]
+struct MergeNode;
+
+#[tokio::main]
+async fn main() -> Result<(), runesys::error::Error> {
+	MergeNode.builder().with_reporter().run().await
+}
@@ -0,0 +1,273 @@
+use std::{
+	cmp::Reverse,
+	collections::{HashMap, hash_map},
+};
+
+use rssflow_service::{
+	ServiceExt2, check_node, payload_type,
+	proto::{
+		feed::{Entry, Feed},
+		node::{
+			DescribeRequest, NodeDescription, OptionSchema, OptionType, PingRequest, PingResponse,
+			ProcessRequest, ProcessResponse, node_service_server::NodeService,
+		},
+	},
+	try_all_from_request,
+};
+use tonic::{Request, Response, Status};
+use tracing::instrument;
+
+use crate::MergeNode;
+
+fn updated(entry: &Entry) -> (i64, i32) {
+	entry
+		.updated
+		.map(|t| (t.seconds, t.nanos))
+		.unwrap_or_default()
+}
+
+/// Identifies the same entry across feeds: its id, or its alternate link if it has none.
+fn key(entry: &Entry) -> Option<String> {
+	if !entry.id.is_empty() {
+		return Some(entry.id.clone());
+	}
+	entry
+		.links
+		.iter()
+		.find(|l| l.rel == "alternate" && !l.href.is_empty())
+		.map(|l| l.href.clone())
+}
+
+/// Combines the feeds' entries, newest first. The title and id default to the inputs' titles and
+/// the first input's id.
+fn merge(feeds: Vec<Feed>, title: Option<String>, id: Option<String>) -> Feed {
+	let title = title.unwrap_or_else(|| {
+		feeds
+			.iter()
+			.map(|f| f.title.as_str())
+			.collect::<Vec<_>>()
+			.join(", ")
+	});
+	let id = id.unwrap_or_else(|| feeds.first().map(|f| f.id.clone()).unwrap_or_default());
+	let updated_at = feeds
+		.iter()
+		.filter_map(|f| f.updated)
+		.max_by_key(|t| (t.seconds, t.nanos));
+
+	// Keep the most recently updated version of each entry, in order of first appearance.
+	let mut entries: Vec<Entry> = Vec::new();
+	let mut index: HashMap<String, usize> = HashMap::new();
+	for feed in feeds {
+		for mut entry in feed.entries {
+			if entry.authors.is_empty() {
+				entry.authors.clone_from(&feed.authors);
+			}
+
+			// Entries that can't be told apart are all kept.
+			let Some(key) = key(&entry) else {
+				entries.push(entry);
+				continue;
+			};
+			match index.entry(key) {
+				hash_map::Entry::Occupied(o) => {
+					let existing = &mut entries[*o.get()];
+					if updated(&entry) > updated(existing) {
+						*existing = entry;
+					}
+				}
+				hash_map::Entry::Vacant(v) => {
+					v.insert(entries.len());
+					entries.push(entry);
+				}
+			}
+		}
+	}
+	entries.sort_by_key(|e| Reverse(updated(e)));
+
+	Feed {
+		title,
+		id,
+		updated: updated_at,
+		entries,
+		..Feed::default()
+	}
+}
+
+#[tonic::async_trait]
+impl NodeService for MergeNode {
+	#[instrument(skip_all)]
+	async fn process(
+		&self,
+		request: Request<ProcessRequest>,
+	) -> Result<Response<ProcessResponse>, Status> {
+		runesys::telemetry::propagation::accept_trace(&request);
+		check_node::<Self>(&request)?;
+		let request = request.into_inner();
+
+		let feeds: Vec<Feed> = try_all_from_request(&request)?;
+
+		let title = request.get_option::<&String>("title").transpose()?.cloned();
+		let id = request
+			.get_option::<&String>("feed_id")
+			.transpose()?
+			.cloned();
+
+		let feed = merge(feeds, title, id);
+
+		Ok(Response::new(ProcessResponse {
+			payload: Some(feed.into()),
+		}))
+	}
+
+	async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
+		Self::respond_to_ping()
+	}
+
+	async fn describe(
+		&self,
+		request: Request<DescribeRequest>,
+	) -> Result<Response<NodeDescription>, Status> {
+		Self::respond_to_describe(NodeDescription {
+			description: "Combines the entries of multiple feeds, newest first".to_string(),
+			input_types: vec![payload_type::<Feed>()],
+			multiple_inputs: true,
+			output_type: payload_type::<Feed>(),
+			options: vec![
+				OptionSchema::optional("title", OptionType::String)
+					.with_description("Title of the merged feed, defaults to the input titles"),
+				OptionSchema::optional("feed_id", OptionType::String)
+					.with_description("Id of the merged feed, defaults to the first input's id"),
+			],
+			..NodeDescription::default()
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use prost_types::Timestamp;
+	use rssflow_service::proto::feed::{Entry, Feed, Link, Person};
+
+	use super::merge;
+
+	fn entry(id: &str, updated: i64) -> Entry {
+		Entry {
+			id: id.to_string(),
+			title: format!("{id} at {updated}"),
+			updated: Some(Timestamp {
+				seconds: updated,
+				nanos: 0,
+			}),
+			..Entry::default()
+		}
+	}
+
+	fn feed(id: &str, title: &str, entries: Vec<Entry>) -> Feed {
+		Feed {
+			id: id.to_string(),
+			title: title.to_string(),
+			entries,
+			..Feed::default()
+		}
+	}
+
+	fn titles(feed: &Feed) -> Vec<&str> {
+		feed.entries.iter().map(|e| e.title.as_str()).collect()
+	}
+
+	#[test]
+	fn keeps_latest_version() {
+		let merged = merge(
+			vec![
+				feed("a", "A", vec![entry("1", 10), entry("2", 30)]),
+				feed("b", "B", vec![entry("1", 20), entry("2", 5)]),
+			],
+			None,
+			None,
+		);
+		assert_eq!(titles(&merged), ["2 at 30", "1 at 20"]);
+	}
+
+	#[test]
+	fn newest_first() {
+		let merged = merge(
+			vec![
+				feed("a", "A", vec![entry("1", 10), entry("3", 30)]),
+				feed("b", "B", vec![entry("2", 20), entry("4", 0)]),
+			],
+			None,
+			None,
+		);
+		assert_eq!(titles(&merged), ["3 at 30", "2 at 20", "1 at 10", "4 at 0"]);
+	}
+
+	#[test]
+	fn without_ids() {
+		let linked = |href: &str, updated: i64| Entry {
+			links: vec![Link {
+				href: href.to_string(),
+				rel: "alternate".to_string(),
+				..Link::default()
+			}],
+			..entry("", updated)
+		};
+
+		let merged = merge(
+			vec![
+				feed(
+					"a",
+					"A",
+					vec![entry("", 10), linked("https://example.org/1", 20)],
+				),
+				feed(
+					"b",
+					"B",
+					vec![entry("", 5), linked("https://example.org/1", 30)],
+				),
+			],
+			None,
+			None,
+		);
+		// Entries without an id are told apart by their link, or not deduplicated at all.
+		assert_eq!(titles(&merged), [" at 30", " at 10", " at 5"]);
+	}
+
+	#[test]
+	fn title_and_id() {
+		let feeds = || vec![feed("a", "A", vec![]), feed("b", "B", vec![])];
+
+		let merged = merge(feeds(), None, None);
+		assert_eq!((merged.title.as_str(), merged.id.as_str()), ("A, B", "a"));
+
+		let merged = merge(feeds(), Some("All".to_string()), Some("all".to_string()));
+		assert_eq!((merged.title.as_str(), merged.id.as_str()), ("All", "all"));
+	}
+
+	#[test]
+	fn inherits_authors() {
+		let author = |name: &str| Person {
+			name: name.to_string(),
+			..Person::default()
+		};
+		let own = Entry {
+			authors: vec![author("Entry")],
+			..entry("2", 20)
+		};
+		let merged = merge(
+			vec![Feed {
+				authors: vec![author("Feed")],
+				..feed("a", "A", vec![entry("1", 10), own])
+			}],
+			None,
+			None,
+		);
+
+		let authors: Vec<&str> = merged
+			.entries
+			.iter()
+			.map(|e| e.authors[0].name.as_str())
+			.collect();
+		assert_eq!(authors, ["Entry", "Feed"]);
+		assert!(merged.authors.is_empty());
+	}
+}
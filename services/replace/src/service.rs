@@ -2,12 +2,12 @@ use std::thread::available_parallelism;
 
 use futures::{StreamExt, stream};
 use rssflow_service::{
-	ServiceExt2, check_node,
+	ServiceExt2, check_node, payload_type,
 	proto::{
 		feed::Feed,
 		node::{
-			Field, PingRequest, PingResponse, ProcessRequest, ProcessResponse,
-			node_service_server::NodeService,
+			DescribeRequest, NodeDescription, OptionSchema, OptionType, PingRequest, PingResponse,
+			ProcessRequest, ProcessResponse, node_service_server::NodeService,
 		},
 	},
 	try_from_request,
@@ -33,17 +33,13 @@ impl NodeService for ReplaceNode {
 		let old: &String = request.get_option_required("old")?;
 		let new: &String = request.get_option_required("new")?;
 
-		let field = request.get_option_required("field").and_then(|f: &f64| {
-			Field::try_from(*f as i32)
-				.map_err(|e| Status::invalid_argument("not a valid field enum value"))
-		})?;
+		let field = request.get_field("field")?;
 
 		feed.entries = stream::iter(feed.entries.into_iter())
 			.map(|mut item| async {
-				let Some(value) = item.value_mut(field) else {
-					return item;
-				};
-				*value = value.replace(old, new);
+				for value in item.values_mut(field) {
+					*value = value.replace(old, new);
+				}
 
 				item
 			})
@@ -59,4 +55,24 @@ impl NodeService for ReplaceNode {
 	async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
 		Self::respond_to_ping()
 	}
+
+	async fn describe(
+		&self,
+		request: Request<DescribeRequest>,
+	) -> Result<Response<NodeDescription>, Status> {
+		Self::respond_to_describe(NodeDescription {
+			description: "Replaces text in an entry field".to_string(),
+			input_types: vec![payload_type::<Feed>()],
+			output_type: payload_type::<Feed>(),
+			options: vec![
+				OptionSchema::required("field", OptionType::Field)
+					.with_description("Entry field to replace text in"),
+				OptionSchema::required("old", OptionType::String)
+					.with_description("Text to replace"),
+				OptionSchema::required("new", OptionType::String)
+					.with_description("Replacement text"),
+			],
+			..NodeDescription::default()
+		})
+	}
 }
@@ -4,12 +4,12 @@ use base64::{Engine, engine::general_purpose};
 use futures::{StreamExt, stream};
 use redis::{AsyncCommands, aio::MultiplexedConnection};
 use rssflow_service::{
-	ServiceExt2, check_node,
+	ServiceExt2, check_node, payload_type,
 	proto::{
 		feed::{Content, Entry, Feed},
 		node::{
-			PingRequest, PingResponse, ProcessRequest, ProcessResponse,
-			node_service_server::NodeService,
+			DescribeRequest, NodeDescription, OptionSchema, OptionType, PingRequest, PingResponse,
+			ProcessRequest, ProcessResponse, node_service_server::NodeService,
 		},
 	},
 	try_from_request,
@@ -103,4 +103,21 @@ impl NodeService for RetrieveNode {
 	async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
 		Self::respond_to_ping()
 	}
+
+	async fn describe(
+		&self,
+		request: Request<DescribeRequest>,
+	) -> Result<Response<NodeDescription>, Status> {
+		Self::respond_to_describe(NodeDescription {
+			description: "Replaces each entry's content with a snippet of its linked page"
+				.to_string(),
+			input_types: vec![payload_type::<Feed>()],
+			output_type: payload_type::<Feed>(),
+			options: vec![
+				OptionSchema::required("selector", OptionType::String)
+					.with_description("CSS selector of the page element to retrieve"),
+			],
+			..NodeDescription::default()
+		})
+	}
 }
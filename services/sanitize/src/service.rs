@@ -2,12 +2,12 @@ use std::thread::available_parallelism;
 
 use futures::{StreamExt, stream};
 use rssflow_service::{
-	ServiceExt2, check_node,
+	ServiceExt2, check_node, payload_type,
 	proto::{
 		feed::Feed,
 		node::{
-			Field, PingRequest, PingResponse, ProcessRequest, ProcessResponse,
-			node_service_server::NodeService,
+			DescribeRequest, NodeDescription, OptionSchema, OptionType, PingRequest, PingResponse,
+			ProcessRequest, ProcessResponse, node_service_server::NodeService,
 		},
 	},
 	try_from_request,
@@ -31,16 +31,13 @@ impl NodeService for SanitizeNode {
 
 		let mut feed: Feed = try_from_request(&request)?;
 
-		let field = request.get_option_required("field").and_then(|f: &f64| {
-			Field::try_from(*f as i32).map_err(|e| Status::invalid_argument(e.to_string()))
-		})?;
+		let field = request.get_field("field")?;
 
 		feed.entries = stream::iter(feed.entries.into_iter())
 			.map(|mut item| async {
-				let Some(value) = item.value_mut(field) else {
-					return item;
-				};
-				*value = self.ammonia.clean(value).to_string();
+				for value in item.values_mut(field) {
+					*value = self.ammonia.clean(value).to_string();
+				}
 
 				item
 			})
@@ -56,4 +53,20 @@ impl NodeService for SanitizeNode {
 	async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
 		Self::respond_to_ping()
 	}
+
+	async fn describe(
+		&self,
+		request: Request<DescribeRequest>,
+	) -> Result<Response<NodeDescription>, Status> {
+		Self::respond_to_describe(NodeDescription {
+			description: "Strips unsafe HTML from an entry field".to_string(),
+			input_types: vec![payload_type::<Feed>()],
+			output_type: payload_type::<Feed>(),
+			options: vec![
+				OptionSchema::required("field", OptionType::Field)
+					.with_description("Entry field to sanitize"),
+			],
+			..NodeDescription::default()
+		})
+	}
 }
@@ -0,0 +1,26 @@
+#![warn(clippy::pedantic)]
+
+use rssflow_service::{ServiceExt, proto, proto::node::node_service_server::NodeServiceServer};
+use runesys::{Service, config::config};
+
+mod service;
+
+#[derive(Service)]
+#[service("Seen")]
+#[server(NodeServiceServer)]
+#[fd_set(proto::FILE_DESCRIPTOR_SET)]
+struct SeenNode {
+	conn: redis::aio::MultiplexedConnection,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+	runesys::tracing::init(&SeenNode::INFO);
+	let config = config();
+
+	let redis = redis::Client::open(config.redis_url.as_str())?;
+	let conn = redis.get_multiplexed_async_connection().await?;
+
+	let node = SeenNode { conn };
+	Ok(node.builder().with_reporter().run().await?)
+}
@@ -0,0 +1,272 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use redis::aio::MultiplexedConnection;
+use rssflow_service::{
+	ServiceExt2, check_node, payload_type,
+	proto::{
+		feed::Feed,
+		node::{
+			DescribeRequest, NodeDescription, OptionSchema, OptionType, PingRequest, PingResponse,
+			ProcessRequest, ProcessResponse, node_service_server::NodeService,
+		},
+	},
+	try_from_request,
+};
+use tonic::{Request, Response, Status};
+use tracing::instrument;
+
+use crate::SeenNode;
+
+enum Mode {
+	/// Drop entries that were seen before.
+	Filter,
+	/// Pass every entry through, setting `seen` on the ones that were seen before.
+	Mark,
+}
+
+enum Scope {
+	/// Entries are tracked separately for every node of every flow.
+	Flow,
+	/// Entries are tracked across all flows.
+	Global,
+}
+
+fn store_key(scope: &Scope, request: &ProcessRequest) -> Result<String, Status> {
+	Ok(match scope {
+		Scope::Global => "rssflow:seen:global".to_string(),
+		Scope::Flow => {
+			let Some(context) = &request.context else {
+				return Err(Status::invalid_argument(
+					"flow scope requires a flow context",
+				));
+			};
+			format!("rssflow:seen:flow:{}:{}", context.flow, context.node)
+		}
+	})
+}
+
+/// Parses the `retention` option, a positive whole number of seconds.
+fn retention(seconds: f64) -> Result<Duration, Status> {
+	if seconds < 1.0 || seconds.fract() != 0.0 || seconds > u64::MAX as f64 {
+		return Err(Status::invalid_argument(format!(
+			"retention must be a positive whole number of seconds, got {seconds}"
+		)));
+	}
+	Ok(Duration::from_secs(seconds as u64))
+}
+
+/// Records the entry ids under `key` as seen at `now`, after forgetting the ones that weren't seen
+/// within `retention`. Returns whether each id was new.
+async fn track(
+	conn: &mut MultiplexedConnection,
+	key: &str,
+	ids: &[&str],
+	now: u64,
+	retention: Duration,
+) -> redis::RedisResult<Vec<bool>> {
+	let mut pipe = redis::pipe();
+	pipe.atomic()
+		.cmd("ZREMRANGEBYSCORE")
+		.arg(key)
+		.arg("-inf")
+		.arg(now.saturating_sub(retention.as_secs()))
+		.ignore();
+	for id in ids {
+		pipe.cmd("ZADD").arg(key).arg("NX").arg(now).arg(id);
+		pipe.cmd("ZADD")
+			.arg(key)
+			.arg("XX")
+			.arg(now)
+			.arg(id)
+			.ignore();
+	}
+	pipe.cmd("EXPIRE")
+		.arg(key)
+		.arg(retention.as_secs())
+		.ignore();
+
+	pipe.query_async(conn).await
+}
+
+#[tonic::async_trait]
+impl NodeService for SeenNode {
+	#[instrument(skip_all)]
+	async fn process(
+		&self,
+		request: Request<ProcessRequest>,
+	) -> Result<Response<ProcessResponse>, Status> {
+		runesys::telemetry::propagation::accept_trace(&request);
+		check_node::<Self>(&request)?;
+		let request = request.into_inner();
+		let mut conn = self.conn.clone();
+
+		let mut feed: Feed = try_from_request(&request)?;
+
+		let mode = match request.get_option::<&String>("mode") {
+			Some(r) => match r?.as_str() {
+				"filter" => Mode::Filter,
+				"mark" => Mode::Mark,
+				m => Err(Status::invalid_argument(format!(
+					"unknown mode {m}: oneof [filter, mark]"
+				)))?,
+			},
+			None => Mode::Filter,
+		};
+
+		let scope = match request.get_option::<&String>("scope") {
+			Some(r) => match r?.as_str() {
+				"flow" => Scope::Flow,
+				"global" => Scope::Global,
+				s => Err(Status::invalid_argument(format!(
+					"unknown scope {s}: oneof [flow, global]"
+				)))?,
+			},
+			None => Scope::Flow,
+		};
+
+		// Entry ids are forgotten once they haven't been seen for this long.
+		let retention = match request.get_option::<&f64>("retention") {
+			Some(r) => retention(*r?)?,
+			None => Duration::from_secs(30 * 24 * 60 * 60), // 30d
+		};
+
+		let key = store_key(&scope, &request)?;
+		let now = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.unwrap_or_default()
+			.as_secs();
+
+		let ids: Vec<&str> = feed.entries.iter().map(|e| e.id.as_str()).collect();
+		let added = track(&mut conn, &key, &ids, now, retention)
+			.await
+			.map_err(|e| Status::internal(e.to_string()))?;
+
+		match mode {
+			Mode::Filter => {
+				let mut added = added.into_iter();
+				feed.entries.retain(|_| added.next().unwrap_or_default());
+			}
+			Mode::Mark => {
+				for (entry, added) in feed.entries.iter_mut().zip(added) {
+					entry.seen = !added;
+				}
+			}
+		}
+
+		Ok(Response::new(ProcessResponse {
+			payload: Some(feed.into()),
+		}))
+	}
+
+	async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
+		Self::respond_to_ping()
+	}
+
+	async fn describe(
+		&self,
+		request: Request<DescribeRequest>,
+	) -> Result<Response<NodeDescription>, Status> {
+		Self::respond_to_describe(NodeDescription {
+			description: "Drops or marks entries that were emitted before".to_string(),
+			input_types: vec![payload_type::<Feed>()],
+			output_type: payload_type::<Feed>(),
+			options: vec![
+				OptionSchema::optional("mode", OptionType::String)
+					.with_description("Drop seen entries, or pass them through with `seen` set")
+					.with_values(&["filter", "mark"])
+					.with_default("filter".to_string()),
+				OptionSchema::optional("scope", OptionType::String)
+					.with_description("Track entries per flow node, or across all flows")
+					.with_values(&["flow", "global"])
+					.with_default("flow".to_string()),
+				OptionSchema::optional("retention", OptionType::Number)
+					.with_description("Seconds an entry is remembered for after it was last seen")
+					.with_default(2_592_000.0),
+			],
+			..NodeDescription::default()
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		process,
+		time::{Duration, SystemTime, UNIX_EPOCH},
+	};
+
+	use redis::aio::MultiplexedConnection;
+	use tonic::Code;
+
+	use super::{retention, track};
+
+	/// Connects to the Redis server at `REDIS_URL`, which the dev environment provides.
+	async fn connection() -> MultiplexedConnection {
+		let url = std::env::var("REDIS_URL").expect("REDIS_URL must be set");
+		redis::Client::open(url)
+			.unwrap()
+			.get_multiplexed_async_connection()
+			.await
+			.unwrap()
+	}
+
+	/// A key no other test run uses.
+	fn key(test: &str) -> String {
+		let nanos = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.unwrap()
+			.as_nanos();
+		format!("rssflow:seen:test:{test}:{}:{nanos}", process::id())
+	}
+
+	#[test]
+	fn valid_retention() {
+		assert_eq!(retention(1.0).unwrap(), Duration::from_secs(1));
+		assert_eq!(retention(86_400.0).unwrap(), Duration::from_secs(86_400));
+	}
+
+	#[test]
+	fn invalid_retention() {
+		for seconds in [0.0, -5.0, 1.5, f64::NAN, f64::INFINITY] {
+			let err = retention(seconds).unwrap_err();
+			assert_eq!(err.code(), Code::InvalidArgument, "{seconds}");
+		}
+	}
+
+	#[tokio::test]
+	async fn seen_on_second_pass() {
+		let mut conn = connection().await;
+		let key = key("second_pass");
+		let retention = Duration::from_secs(60);
+
+		let added = track(&mut conn, &key, &["a", "b"], 1000, retention)
+			.await
+			.unwrap();
+		assert_eq!(added, [true, true]);
+		let added = track(&mut conn, &key, &["b", "c"], 1010, retention)
+			.await
+			.unwrap();
+		assert_eq!(added, [false, true]);
+	}
+
+	#[tokio::test]
+	async fn forgotten_after_retention() {
+		let mut conn = connection().await;
+		let key = key("retention");
+		let retention = Duration::from_secs(60);
+
+		track(&mut conn, &key, &["a", "b"], 1000, retention)
+			.await
+			.unwrap();
+		// Seeing an entry again keeps it for another retention window.
+		let added = track(&mut conn, &key, &["b"], 1050, retention)
+			.await
+			.unwrap();
+		assert_eq!(added, [false]);
+
+		let added = track(&mut conn, &key, &["a", "b"], 1100, retention)
+			.await
+			.unwrap();
+		assert_eq!(added, [true, false]);
+	}
+}
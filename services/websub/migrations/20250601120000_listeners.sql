@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS listener
+(
+    subscription UUID NOT NULL REFERENCES subscription (uuid) ON DELETE CASCADE,
+    node_name    TEXT NOT NULL,
+    address      TEXT NOT NULL,
+    flow         TEXT NOT NULL,
+    PRIMARY KEY (subscription, node_name, address, flow)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS hub_subscriber
+(
+    topic     TEXT        NOT NULL,
+    callback  TEXT        NOT NULL,
+    secret    TEXT,
+    lease_end TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (topic, callback)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE subscription
+    ADD COLUMN require_signature BOOLEAN DEFAULT TRUE                            NOT NULL,
+    ADD COLUMN algorithms        TEXT[]  DEFAULT '{sha256,sha384,sha512}'::TEXT[] NOT NULL;
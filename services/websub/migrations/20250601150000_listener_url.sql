@@ -0,0 +1,5 @@
+-- URL the listening node fetches the topic from, the topic itself if empty.
+ALTER TABLE listener
+    ADD COLUMN url TEXT DEFAULT '' NOT NULL,
+    DROP CONSTRAINT listener_pkey,
+    ADD PRIMARY KEY (subscription, node_name, address, flow, url);
@@ -0,0 +1,4 @@
+-- The same topic may be advertised by several hubs, each needing its own subscription.
+ALTER TABLE subscription
+    DROP CONSTRAINT subscription_topic_key,
+    ADD CONSTRAINT subscription_topic_hub_key UNIQUE (topic, hub);
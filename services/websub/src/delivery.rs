@@ -0,0 +1,310 @@
+//! Which pushes to accept: signature policy and replay protection.
+
+use std::{
+	collections::HashMap,
+	fmt,
+	str::FromStr,
+	sync::{LazyLock, Mutex, OnceLock},
+	time::{Duration, Instant},
+};
+
+use axum::http::HeaderMap;
+use figment::{Figment, providers::Serialized};
+use opentelemetry::{KeyValue, global, metrics::Counter};
+use rssflow_service::proto::websub::SignaturePolicy;
+use serde::{Deserialize, Serialize};
+use serde_with::{DurationSeconds, serde_as};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::ws::{X_HUB_SIGNATURE, X_HUB_SIGNATURE_256, XHubSignature};
+
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeliveryConfig {
+	/// Whether unsigned pushes are rejected, unless a subscription says otherwise.
+	pub require_signature: bool,
+	/// Accepted HMAC algorithms, unless a subscription says otherwise.
+	pub algorithms: Vec<String>,
+	/// Identical pushes received within this many seconds are dropped.
+	#[serde_as(as = "DurationSeconds<u64>")]
+	pub dedup_window: Duration,
+}
+
+impl Default for DeliveryConfig {
+	fn default() -> Self {
+		DeliveryConfig {
+			require_signature: true,
+			algorithms: vec!["sha256".into(), "sha384".into(), "sha512".into()],
+			dedup_window: Duration::from_secs(5 * 60),
+		}
+	}
+}
+
+pub fn delivery_config() -> &'static DeliveryConfig {
+	static CONFIG: OnceLock<DeliveryConfig> = OnceLock::new();
+
+	CONFIG.get_or_init(|| {
+		Figment::from(Serialized::default("delivery", DeliveryConfig::default()))
+			.merge(runesys::config::FIGMENT.clone())
+			.extract_inner("delivery")
+			.unwrap()
+	})
+}
+
+/// The policy a subscription is created with, filling in the configured defaults.
+pub fn resolve(policy: Option<SignaturePolicy>) -> (bool, Vec<String>) {
+	let config = delivery_config();
+	match policy {
+		Some(policy) if policy.algorithms.is_empty() => {
+			(policy.require_signature, config.algorithms.clone())
+		}
+		Some(policy) => (policy.require_signature, policy.algorithms),
+		None => (config.require_signature, config.algorithms.clone()),
+	}
+}
+
+/// Combines a subscription's policy with the one another listener asks for, so that adding a
+/// listener never weakens it: a signature stays required once any listener requires one and only
+/// algorithms both accept remain.
+///
+/// Returns `None` if the two share no algorithm.
+pub fn strictest(
+	(require_a, algorithms_a): (bool, Vec<String>),
+	(require_b, algorithms_b): (bool, Vec<String>),
+) -> Option<(bool, Vec<String>)> {
+	let algorithms: Vec<String> = algorithms_a
+		.into_iter()
+		.filter(|a| algorithms_b.contains(a))
+		.collect();
+	if algorithms.is_empty() {
+		return None;
+	}
+	Some((require_a || require_b, algorithms))
+}
+
+/// Why a push was dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+	Unsigned,
+	Malformed,
+	Algorithm,
+	Mismatch,
+	Duplicate,
+}
+
+impl Rejection {
+	fn as_str(self) -> &'static str {
+		match self {
+			Rejection::Unsigned => "unsigned",
+			Rejection::Malformed => "malformed",
+			Rejection::Algorithm => "algorithm",
+			Rejection::Mismatch => "mismatch",
+			Rejection::Duplicate => "duplicate",
+		}
+	}
+
+	/// Counts the rejection in the `websub.push.rejected` metric.
+	pub fn record(self) {
+		static REJECTED: LazyLock<Counter<u64>> = LazyLock::new(|| {
+			global::meter("rssflow-websub")
+				.u64_counter("websub.push.rejected")
+				.with_description("WebSub pushes that were dropped, by reason")
+				.build()
+		});
+
+		REJECTED.add(1, &[KeyValue::new("reason", self.as_str())]);
+	}
+}
+
+impl fmt::Display for Rejection {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Rejection::Unsigned => "missing signature",
+			Rejection::Malformed => "malformed signature",
+			Rejection::Algorithm => "signature algorithm not allowed",
+			Rejection::Mismatch => "signature mismatch",
+			Rejection::Duplicate => "duplicate push",
+		})
+	}
+}
+
+/// Checks the push's signature against the subscription's secret and policy.
+///
+/// `X-Hub-Signature-256` takes precedence over `X-Hub-Signature` when both are sent.
+pub fn verify(
+	headers: &HeaderMap,
+	body: &[u8],
+	secret: &str,
+	require_signature: bool,
+	algorithms: &[String],
+) -> Result<(), Rejection> {
+	let header = headers
+		.get(X_HUB_SIGNATURE_256)
+		.map(|v| (v, true))
+		.or_else(|| headers.get(X_HUB_SIGNATURE).map(|v| (v, false)));
+	let Some((value, sha256_header)) = header else {
+		return if require_signature {
+			Err(Rejection::Unsigned)
+		} else {
+			Ok(())
+		};
+	};
+
+	let signature = value
+		.to_str()
+		.ok()
+		.and_then(|s| XHubSignature::from_str(s).ok())
+		.ok_or(Rejection::Malformed)?;
+	if sha256_header && signature.method() != "sha256" {
+		return Err(Rejection::Malformed);
+	}
+	if !algorithms.iter().any(|a| a == signature.method()) {
+		return Err(Rejection::Algorithm);
+	}
+
+	match signature.verify(secret.as_bytes(), body) {
+		Ok(true) => Ok(()),
+		Ok(false) => Err(Rejection::Mismatch),
+		Err(_) => Err(Rejection::Malformed),
+	}
+}
+
+/// Bodies recently received per subscription, for dropping redelivered pushes.
+#[derive(Debug, Default)]
+pub struct Seen(Mutex<HashMap<(Uuid, [u8; 32]), Instant>>);
+
+impl Seen {
+	/// Records the push, returning whether the same body arrived within `window`.
+	pub fn check(&self, uuid: Uuid, body: &[u8], window: Duration) -> bool {
+		let digest: [u8; 32] = Sha256::digest(body).into();
+		let now = Instant::now();
+
+		let mut seen = self.0.lock().unwrap();
+		seen.retain(|_, at| now.duration_since(*at) < window);
+		seen.insert((uuid, digest), now).is_some()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use axum::http::{HeaderMap, HeaderName, HeaderValue};
+	use sha2::{Sha256, Sha512};
+	use uuid::Uuid;
+
+	use super::{Rejection, Seen, strictest, verify};
+	use crate::ws::{X_HUB_SIGNATURE, X_HUB_SIGNATURE_256, mac};
+
+	const SECRET: &str = "secret";
+	const BODY: &[u8] = b"<feed/>";
+
+	fn algorithms() -> Vec<String> {
+		vec!["sha256".to_string()]
+	}
+
+	fn signed(header: HeaderName, method: &str, signature: &[u8]) -> HeaderMap {
+		let mut headers = HeaderMap::new();
+		let value = format!("{method}={}", hex::encode(signature));
+		headers.insert(header, HeaderValue::from_str(&value).unwrap());
+		headers
+	}
+
+	#[test]
+	fn accepts_either_header() {
+		let signature = mac::sign_hmac::<Sha256>(SECRET.as_bytes(), BODY).unwrap();
+
+		for header in [X_HUB_SIGNATURE, X_HUB_SIGNATURE_256] {
+			let headers = signed(header, "sha256", &signature);
+			assert_eq!(verify(&headers, BODY, SECRET, true, &algorithms()), Ok(()));
+		}
+	}
+
+	#[test]
+	fn unsigned() {
+		let headers = HeaderMap::new();
+		assert_eq!(
+			verify(&headers, BODY, SECRET, true, &algorithms()),
+			Err(Rejection::Unsigned)
+		);
+		assert_eq!(verify(&headers, BODY, SECRET, false, &algorithms()), Ok(()));
+	}
+
+	#[test]
+	fn malformed() {
+		let mut headers = HeaderMap::new();
+		headers.insert(X_HUB_SIGNATURE, HeaderValue::from_static("sha256"));
+		assert_eq!(
+			verify(&headers, BODY, SECRET, true, &algorithms()),
+			Err(Rejection::Malformed)
+		);
+	}
+
+	#[test]
+	fn disallowed_algorithm() {
+		let signature = mac::sign_hmac::<Sha512>(SECRET.as_bytes(), BODY).unwrap();
+		let headers = signed(X_HUB_SIGNATURE, "sha512", &signature);
+		assert_eq!(
+			verify(&headers, BODY, SECRET, true, &algorithms()),
+			Err(Rejection::Algorithm)
+		);
+	}
+
+	#[test]
+	fn mismatch() {
+		let signature = mac::sign_hmac::<Sha256>(b"other", BODY).unwrap();
+		let headers = signed(X_HUB_SIGNATURE_256, "sha256", &signature);
+		assert_eq!(
+			verify(&headers, BODY, SECRET, true, &algorithms()),
+			Err(Rejection::Mismatch)
+		);
+	}
+
+	#[test]
+	fn strictest_policy() {
+		let policy = |require: bool, algorithms: &[&str]| {
+			(
+				require,
+				algorithms
+					.iter()
+					.map(ToString::to_string)
+					.collect::<Vec<_>>(),
+			)
+		};
+
+		assert_eq!(
+			strictest(
+				policy(true, &["sha256", "sha512"]),
+				policy(false, &["sha1", "sha256"])
+			),
+			Some(policy(true, &["sha256"]))
+		);
+		assert_eq!(
+			strictest(policy(false, &["sha256"]), policy(true, &["sha256"])),
+			Some(policy(true, &["sha256"]))
+		);
+		assert_eq!(
+			strictest(policy(false, &["sha256"]), policy(false, &["sha256"])),
+			Some(policy(false, &["sha256"]))
+		);
+		assert_eq!(
+			strictest(policy(true, &["sha512"]), policy(true, &["sha1"])),
+			None
+		);
+	}
+
+	#[test]
+	fn duplicates() {
+		let seen = Seen::default();
+		let uuid = Uuid::now_v7();
+		let window = Duration::from_secs(60);
+
+		assert!(!seen.check(uuid, BODY, window));
+		assert!(seen.check(uuid, BODY, window));
+		assert!(!seen.check(uuid, b"<feed></feed>", window));
+		assert!(!seen.check(Uuid::now_v7(), BODY, window));
+		assert!(!seen.check(uuid, BODY, Duration::ZERO));
+	}
+}
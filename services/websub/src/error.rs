@@ -0,0 +1,36 @@
+use tonic::Status;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("Missing sub")]
+	MissingSub,
+	#[error("Missing node")]
+	MissingNode,
+	#[error("Database not ready")]
+	NoDatabase,
+	#[error("Database error: {0}")]
+	Database(#[from] sqlx::Error),
+	#[error("No such subscription")]
+	NotFound,
+	#[error("Signature policy shares no algorithm with the subscription's: {0}")]
+	PolicyConflict(String),
+	#[error("Hub request failed: {0:#}")]
+	Hub(anyhow::Error),
+}
+
+impl From<Error> for Status {
+	fn from(err: Error) -> Self {
+		let message = err.to_string();
+		match err {
+			Error::MissingSub | Error::MissingNode => Status::invalid_argument(message),
+			Error::NotFound => Status::not_found(message),
+			Error::PolicyConflict(_) => Status::failed_precondition(message),
+			Error::NoDatabase
+			| Error::Hub(_)
+			| Error::Database(
+				sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_),
+			) => Status::unavailable(message),
+			Error::Database(_) => Status::internal(message),
+		}
+	}
+}
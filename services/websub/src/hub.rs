@@ -0,0 +1,107 @@
+use anyhow::anyhow;
+use rssflow_service::{config::config, proto::websub::WebSub};
+use tracing::info;
+use url::Url;
+use uuid::Uuid;
+
+use crate::WebSubSVC;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Mode {
+	Subscribe,
+	Unsubscribe,
+}
+
+impl Mode {
+	fn as_str(self) -> &'static str {
+		match self {
+			Mode::Subscribe => "subscribe",
+			Mode::Unsubscribe => "unsubscribe",
+		}
+	}
+}
+
+/// Asks the hub to (un)subscribe our callback for `uuid`. The hub confirms asynchronously, by
+/// verifying the intent at the callback.
+pub async fn request(sub: &WebSub, uuid: Uuid, secret: &str, mode: Mode) -> anyhow::Result<()> {
+	let public_url = config::<WebSubSVC>().public_url.as_ref();
+	request_with(public_url, sub, uuid, secret, mode).await
+}
+
+async fn request_with(
+	public_url: Option<&Url>,
+	sub: &WebSub,
+	uuid: Uuid,
+	secret: &str,
+	mode: Mode,
+) -> anyhow::Result<()> {
+	let Some(public_url) = public_url else {
+		return Err(anyhow!("Public url unset"));
+	};
+
+	let callback = format!("{public_url}websub/{uuid}");
+	let mut form = vec![
+		("hub.callback", callback.as_str()),
+		("hub.mode", mode.as_str()),
+		("hub.topic", &sub.topic),
+	];
+	if matches!(mode, Mode::Subscribe) {
+		form.push(("hub.secret", secret));
+	}
+
+	let resp = reqwest::Client::new()
+		.post(&sub.hub)
+		.form(&form)
+		.send()
+		.await?;
+	info!(
+		"Hub {} answered {} to {} `{}`",
+		sub.hub,
+		resp.status(),
+		mode.as_str(),
+		sub.topic
+	);
+	resp.error_for_status()?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use rssflow_service::proto::websub::WebSub;
+	use url::Url;
+	use uuid::Uuid;
+
+	use super::{Mode, request_with};
+
+	fn sub() -> WebSub {
+		WebSub {
+			// Nothing listens on the discard port, so hub requests fail.
+			hub: "http://127.0.0.1:9/".to_string(),
+			topic: "https://example.org/feed.atom".to_string(),
+		}
+	}
+
+	#[tokio::test]
+	async fn public_url_unset() {
+		let err = request_with(None, &sub(), Uuid::now_v7(), "secret", Mode::Subscribe)
+			.await
+			.unwrap_err();
+		assert_eq!(err.to_string(), "Public url unset");
+	}
+
+	#[tokio::test]
+	async fn hub_unreachable() {
+		let public_url = Url::parse("https://rssflow.example.com/").unwrap();
+		let err = request_with(
+			Some(&public_url),
+			&sub(),
+			Uuid::now_v7(),
+			"secret",
+			Mode::Subscribe,
+		)
+		.await
+		.unwrap_err();
+		assert!(err.is::<reqwest::Error>(), "{err:#}");
+	}
+}
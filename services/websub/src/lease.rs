@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use rssflow_service::proto::websub::WebSub;
+use sqlx::{PgPool, types::chrono::Utc};
+use tracing::{info, warn};
+
+use crate::hub::{self, Mode};
+
+/// How often leases are checked.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Leases are renewed this long before they end, leaving the hub time to verify.
+const RENEW_BEFORE: Duration = Duration::from_secs(60 * 60);
+
+/// Renews subscriptions whose lease is about to end.
+pub async fn renew(pool: &PgPool) -> Result<(), sqlx::Error> {
+	let renew_before = Utc::now() + RENEW_BEFORE;
+	let records = sqlx::query!(
+		"SELECT uuid, topic, hub, secret FROM subscription WHERE subscribed AND lease_end < $1",
+		renew_before
+	)
+	.fetch_all(pool)
+	.await?;
+
+	for record in records {
+		let sub = WebSub {
+			topic: record.topic,
+			hub: record.hub,
+		};
+		info!("Renewing lease of `{}`", sub.topic);
+		if let Err(err) = hub::request(&sub, record.uuid, &record.secret, Mode::Subscribe).await {
+			warn!("Renewing `{}` failed: {err:#}", sub.topic);
+		}
+	}
+
+	Ok(())
+}
+
+/// Brings the hubs in line with the database, after changes that may have been missed while
+/// the service was down:
+/// - subscriptions nobody listens to anymore are unsubscribed,
+/// - unverified or lapsed subscriptions are requested again,
+/// - unsubscriptions the hub never confirmed are requested again, or dropped once their lease
+///   ended anyway.
+pub async fn reconcile(pool: &PgPool) -> Result<(), sqlx::Error> {
+	let records = sqlx::query!(
+		r#"SELECT uuid, topic, hub, secret, subscribed, lease_end, EXISTS(SELECT 1 FROM listener WHERE subscription = uuid) AS "listened!" FROM subscription"#
+	)
+	.fetch_all(pool)
+	.await?;
+
+	let now = Utc::now();
+	for record in records {
+		let sub = WebSub {
+			topic: record.topic,
+			hub: record.hub,
+		};
+		let lapsed = record.lease_end.is_none_or(|end| end < now);
+
+		let mode = match (record.subscribed, record.listened, lapsed) {
+			(true, true, true) => Mode::Subscribe,
+			(true, true, false) => continue,
+			(true, false, _) => {
+				sqlx::query!(
+					"UPDATE subscription SET subscribed = $2 WHERE uuid = $1",
+					record.uuid,
+					false
+				)
+				.execute(pool)
+				.await?;
+				Mode::Unsubscribe
+			}
+			(false, _, false) => Mode::Unsubscribe,
+			(false, _, true) => {
+				sqlx::query!("DELETE FROM subscription WHERE uuid = $1", record.uuid)
+					.execute(pool)
+					.await?;
+				info!("Dropped lapsed subscription to `{}`", sub.topic);
+				continue;
+			}
+		};
+
+		if let Err(err) = hub::request(&sub, record.uuid, &record.secret, mode).await {
+			warn!("Reconciling `{}` failed: {err:#}", sub.topic);
+		}
+	}
+
+	Ok(())
+}
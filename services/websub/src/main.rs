@@ -1,21 +1,25 @@
 #![warn(clippy::pedantic)]
 
 use std::{
-	collections::{HashMap, HashSet},
 	ops::Deref,
-	sync::{Arc, Mutex},
+	sync::{Arc, OnceLock},
+	time::Duration,
 };
 
-use rssflow_service::proto::{
-	node::NodeMeta,
-	websub::{WebSub, web_sub_service_server::WebSubServiceServer},
-};
+use rssflow_service::proto::websub::{WebSubEvent, web_sub_service_server::WebSubServiceServer};
 use runesys::Service;
+use sqlx::PgPool;
+use tokio::sync::broadcast;
+use tracing::error;
 use url::Url;
-use uuid::Uuid;
 
 use crate::router::app;
 
+mod delivery;
+mod error;
+mod hub;
+mod lease;
+mod publish;
 pub mod router;
 mod service;
 mod ws;
@@ -27,16 +31,27 @@ pub async fn websub_check(public_url: &Url) -> anyhow::Result<()> {
 	Ok(())
 }
 
-#[derive(Debug, Default, Clone)]
-pub struct Subscription {
-	web_sub: WebSub,
-	nodes: HashSet<NodeMeta>,
-}
+/// Pushes buffered per `Receive` stream before slow receivers miss some.
+const EVENT_BUFFER: usize = 64;
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct WebSubInner {
-	subscriptions: Mutex<HashMap<Uuid, Subscription>>,
-	ws: Mutex<HashMap<WebSub, Uuid>>,
+	/// Set once the database is connected and migrated.
+	pool: OnceLock<PgPool>,
+	/// Verified pushes, for `Receive` streams.
+	events: broadcast::Sender<WebSubEvent>,
+	/// Recently received pushes, to drop redeliveries.
+	seen: delivery::Seen,
+}
+
+impl Default for WebSubInner {
+	fn default() -> Self {
+		WebSubInner {
+			pool: OnceLock::new(),
+			events: broadcast::channel(EVENT_BUFFER).0,
+			seen: delivery::Seen::default(),
+		}
+	}
 }
 
 #[derive(Service, Debug, Clone, Default)]
@@ -59,9 +74,47 @@ async fn main() -> Result<(), runesys::error::Error> {
 	let svc = WebSubSVC::default();
 	let app = app(svc.clone());
 
+	let lease_task = {
+		let svc = svc.clone();
+		async move {
+			let pool = loop {
+				if let Some(pool) = svc.pool.get() {
+					break pool;
+				}
+				tokio::time::sleep(Duration::from_secs(1)).await;
+			};
+
+			if let Err(err) = lease::reconcile(pool).await {
+				error!("Reconciling subscriptions failed: {err}");
+			}
+			loop {
+				tokio::time::sleep(lease::CHECK_INTERVAL).await;
+				if let Err(err) = lease::renew(pool).await {
+					error!("Renewing leases failed: {err}");
+				}
+				if let Err(err) = publish::expire(pool).await {
+					error!("Expiring hub subscribers failed: {err}");
+				}
+			}
+		}
+	};
+
 	svc.builder()
-		.with_pg(|pool| async move { sqlx::migrate!().run(&pool).await })?
+		.with_pg({
+			let svc = svc.clone();
+			move |pool| {
+				let svc = svc.clone();
+				async move {
+					let migrated = sqlx::migrate!().run(&pool).await;
+					if migrated.is_ok() {
+						let _ = svc.pool.set(pool);
+					}
+					migrated
+				}
+			}
+		})?
 		.with_http(app)
+		.with_task(lease_task)
 		.run()
 		.await
 }
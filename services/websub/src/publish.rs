@@ -0,0 +1,447 @@
+//! A hub for rssflow's own feeds, which pushes them to subscribers whenever rssflow publishes.
+
+use std::{sync::OnceLock, time::Duration};
+
+use axum::{
+	Extension, Form,
+	body::Bytes,
+	http::{HeaderValue, StatusCode, header},
+	response::IntoResponse,
+};
+use figment::{Figment, providers::Serialized};
+use rssflow_service::config::config;
+use serde::{Deserialize, Serialize};
+use serde_with::{DurationSeconds, serde_as};
+use sha2::Sha256;
+use sqlx::{PgPool, types::chrono::Utc};
+use tracing::{error, info, instrument, warn};
+use url::Url;
+
+use crate::{
+	WebSubSVC,
+	ws::{X_HUB_SIGNATURE, generate_hmac_secret, mac},
+};
+
+const DEFAULT_LEASE: Duration = Duration::from_secs(10 * 24 * 60 * 60);
+const MAX_LEASE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// Longest secret subscribers may send, per the WebSub spec.
+const MAX_SECRET_LENGTH: usize = 200;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HubConfig {
+	/// Only topics under this URL can be subscribed to or published, e.g. rssflow's public URL.
+	/// Publishing is refused without one.
+	pub topic_prefix: Option<Url>,
+}
+
+fn hub_config() -> &'static HubConfig {
+	static CONFIG: OnceLock<HubConfig> = OnceLock::new();
+
+	CONFIG.get_or_init(|| {
+		Figment::from(Serialized::default("hub", HubConfig::default()))
+			.merge(runesys::config::FIGMENT.clone())
+			.extract_inner("hub")
+			.unwrap()
+	})
+}
+
+#[serde_as]
+#[derive(Deserialize, Debug)]
+#[serde(tag = "hub.mode", rename_all = "lowercase")]
+pub enum HubRequest {
+	Subscribe {
+		#[serde(rename = "hub.topic")]
+		topic: String,
+		#[serde(rename = "hub.callback")]
+		callback: String,
+		#[serde_as(as = "Option<DurationSeconds<String>>")]
+		#[serde(rename = "hub.lease_seconds", default)]
+		lease_seconds: Option<Duration>,
+		#[serde(rename = "hub.secret", default)]
+		secret: Option<String>,
+	},
+	Unsubscribe {
+		#[serde(rename = "hub.topic")]
+		topic: String,
+		#[serde(rename = "hub.callback")]
+		callback: String,
+	},
+	/// Announces new content at `url`, which the hub then fetches and distributes.
+	Publish {
+		#[serde(rename = "hub.url")]
+		url: String,
+	},
+}
+
+fn hub_url() -> Option<Url> {
+	config::<WebSubSVC>()
+		.public_url
+		.as_ref()
+		.and_then(|url| url.join("websub/hub").ok())
+}
+
+/// Asks the subscriber to confirm the (un)subscription by echoing a challenge.
+async fn verify_intent(
+	mode: &str,
+	topic: &str,
+	callback: &str,
+	lease: Option<Duration>,
+) -> anyhow::Result<bool> {
+	let challenge = generate_hmac_secret();
+	let mut query = vec![
+		("hub.mode", mode.to_string()),
+		("hub.topic", topic.to_string()),
+		("hub.challenge", challenge.clone()),
+	];
+	if let Some(lease) = lease {
+		query.push(("hub.lease_seconds", lease.as_secs().to_string()));
+	}
+
+	let resp = reqwest::Client::new()
+		.get(callback)
+		.query(&query)
+		.send()
+		.await?;
+	Ok(resp.status().is_success() && resp.text().await? == challenge)
+}
+
+async fn subscribe(
+	pool: PgPool,
+	topic: String,
+	callback: String,
+	lease: Duration,
+	secret: Option<String>,
+) -> anyhow::Result<()> {
+	if !verify_intent("subscribe", &topic, &callback, Some(lease)).await? {
+		info!("{callback} didn't confirm its subscription to `{topic}`");
+		return Ok(());
+	}
+
+	let lease_end = Utc::now() + lease;
+	sqlx::query!(
+		"INSERT INTO hub_subscriber (topic, callback, secret, lease_end) VALUES ($1, $2, $3, $4) ON CONFLICT (topic, callback) DO UPDATE SET secret = EXCLUDED.secret, lease_end = EXCLUDED.lease_end",
+		topic,
+		callback,
+		secret,
+		lease_end
+	)
+	.execute(&pool)
+	.await?;
+
+	info!("{callback} subscribed to `{topic}`");
+	Ok(())
+}
+
+async fn unsubscribe(pool: PgPool, topic: String, callback: String) -> anyhow::Result<()> {
+	if !verify_intent("unsubscribe", &topic, &callback, None).await? {
+		info!("{callback} didn't confirm its unsubscription from `{topic}`");
+		return Ok(());
+	}
+
+	sqlx::query!(
+		"DELETE FROM hub_subscriber WHERE topic = $1 AND callback = $2",
+		topic,
+		callback
+	)
+	.execute(&pool)
+	.await?;
+
+	info!("{callback} unsubscribed from `{topic}`");
+	Ok(())
+}
+
+/// Fetches the topic and pushes it to every subscriber, signed with their secret.
+async fn distribute(pool: PgPool, topic: String) -> anyhow::Result<()> {
+	let subscribers = sqlx::query!(
+		"SELECT callback, secret FROM hub_subscriber WHERE topic = $1 AND lease_end > now()",
+		topic
+	)
+	.fetch_all(&pool)
+	.await?;
+	if subscribers.is_empty() {
+		return Ok(());
+	}
+
+	let client = reqwest::Client::new();
+	let resp = client.get(&topic).send().await?.error_for_status()?;
+	let content_type = resp
+		.headers()
+		.get(header::CONTENT_TYPE)
+		.cloned()
+		.unwrap_or(HeaderValue::from_static("application/octet-stream"));
+	let body: Bytes = resp.bytes().await?;
+
+	let mut link = format!("<{topic}>; rel=\"self\"");
+	if let Some(hub) = hub_url() {
+		link = format!("<{hub}>; rel=\"hub\", {link}");
+	}
+
+	for subscriber in subscribers {
+		let mut request = client
+			.post(&subscriber.callback)
+			.header(header::CONTENT_TYPE, content_type.clone())
+			.header(header::LINK, &link)
+			.body(body.clone());
+		if let Some(secret) = &subscriber.secret {
+			let signature = mac::sign_hmac::<Sha256>(secret.as_bytes(), &body)?;
+			request = request.header(
+				X_HUB_SIGNATURE,
+				format!("sha256={}", hex::encode(signature)),
+			);
+		}
+
+		match request.send().await {
+			Ok(resp) if resp.status().is_success() => {}
+			Ok(resp) => warn!(
+				"{} answered {} to a push of `{topic}`",
+				subscriber.callback,
+				resp.status()
+			),
+			Err(err) => warn!("Pushing `{topic}` to {} failed: {err}", subscriber.callback),
+		}
+	}
+
+	Ok(())
+}
+
+/// Drops subscribers whose lease ended.
+pub async fn expire(pool: &PgPool) -> Result<(), sqlx::Error> {
+	sqlx::query!("DELETE FROM hub_subscriber WHERE lease_end < now()")
+		.execute(pool)
+		.await?;
+	Ok(())
+}
+
+/// The lease a subscriber gets for the one it asked for.
+fn lease(requested: Option<Duration>) -> Duration {
+	requested.unwrap_or(DEFAULT_LEASE).min(MAX_LEASE)
+}
+
+fn accepts_topic(prefix: Option<&Url>, topic: &str) -> bool {
+	prefix.is_none_or(|prefix| topic.starts_with(prefix.as_str()))
+}
+
+/// Rejects requests for topics outside `prefix` and subscriptions with oversized secrets.
+///
+/// Publishing needs a prefix, as the hub would otherwise fetch any URL it's sent.
+fn check(request: &HubRequest, prefix: Option<&Url>) -> Result<(), (StatusCode, String)> {
+	let topic = match request {
+		HubRequest::Subscribe { topic, .. } | HubRequest::Unsubscribe { topic, .. } => topic,
+		HubRequest::Publish { .. } if prefix.is_none() => {
+			return Err((
+				StatusCode::FORBIDDEN,
+				"Publishing requires a topic prefix".to_string(),
+			));
+		}
+		HubRequest::Publish { url } => url,
+	};
+	if !accepts_topic(prefix, topic) {
+		return Err((StatusCode::NOT_FOUND, format!("Unknown topic: {topic}")));
+	}
+
+	let oversized = matches!(
+		request,
+		HubRequest::Subscribe { secret: Some(secret), .. } if secret.len() >= MAX_SECRET_LENGTH
+	);
+	if oversized {
+		return Err((StatusCode::BAD_REQUEST, "Secret too long".to_string()));
+	}
+	Ok(())
+}
+
+#[instrument(skip_all)]
+pub async fn hub(
+	Extension(pool): Extension<PgPool>,
+	Form(request): Form<HubRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	check(&request, hub_config().topic_prefix.as_ref())?;
+
+	// Verification and distribution happen in the background, as the spec expects.
+	tokio::spawn(async move {
+		let result = match request {
+			HubRequest::Subscribe {
+				topic,
+				callback,
+				lease_seconds,
+				secret,
+			} => subscribe(pool, topic, callback, lease(lease_seconds), secret).await,
+			HubRequest::Unsubscribe { topic, callback } => unsubscribe(pool, topic, callback).await,
+			HubRequest::Publish { url } => distribute(pool, url).await,
+		};
+		if let Err(err) = result {
+			error!("Hub request failed: {err:#}");
+		}
+	});
+
+	Ok(StatusCode::ACCEPTED)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use axum::http::StatusCode;
+	use url::Url;
+
+	use super::{
+		DEFAULT_LEASE, HubRequest, MAX_LEASE, MAX_SECRET_LENGTH, accepts_topic, check, lease,
+	};
+
+	fn decode(form: &str) -> HubRequest {
+		serde_urlencoded::from_str(form).unwrap()
+	}
+
+	fn subscribe(topic: &str, secret: Option<String>) -> HubRequest {
+		HubRequest::Subscribe {
+			topic: topic.to_string(),
+			callback: "https://reader.example.org/callback".to_string(),
+			lease_seconds: None,
+			secret,
+		}
+	}
+
+	#[test]
+	fn decode_subscribe() {
+		let request = decode(
+			"hub.mode=subscribe&hub.topic=https%3A%2F%2Frssflow.example.com%2Fflow%2Fnews\
+			&hub.callback=https%3A%2F%2Freader.example.org%2Fcallback\
+			&hub.lease_seconds=3600&hub.secret=s3cret",
+		);
+		let HubRequest::Subscribe {
+			topic,
+			callback,
+			lease_seconds,
+			secret,
+		} = request
+		else {
+			panic!("expected a subscription, got {request:?}");
+		};
+		assert_eq!(topic, "https://rssflow.example.com/flow/news");
+		assert_eq!(callback, "https://reader.example.org/callback");
+		assert_eq!(lease_seconds, Some(Duration::from_secs(3600)));
+		assert_eq!(secret.as_deref(), Some("s3cret"));
+
+		let request = decode(
+			"hub.mode=subscribe&hub.topic=https%3A%2F%2Frssflow.example.com%2Fflow%2Fnews\
+			&hub.callback=https%3A%2F%2Freader.example.org%2Fcallback",
+		);
+		assert!(matches!(
+			request,
+			HubRequest::Subscribe {
+				lease_seconds: None,
+				secret: None,
+				..
+			}
+		));
+	}
+
+	#[test]
+	fn decode_unsubscribe() {
+		let request = decode(
+			"hub.mode=unsubscribe&hub.topic=https%3A%2F%2Frssflow.example.com%2Fflow%2Fnews\
+			&hub.callback=https%3A%2F%2Freader.example.org%2Fcallback",
+		);
+		assert!(matches!(
+			request,
+			HubRequest::Unsubscribe { topic, callback }
+				if topic == "https://rssflow.example.com/flow/news"
+					&& callback == "https://reader.example.org/callback"
+		));
+	}
+
+	#[test]
+	fn decode_publish() {
+		let request =
+			decode("hub.mode=publish&hub.url=https%3A%2F%2Frssflow.example.com%2Fflow%2Fnews");
+		assert!(matches!(
+			request,
+			HubRequest::Publish { url } if url == "https://rssflow.example.com/flow/news"
+		));
+	}
+
+	#[test]
+	fn decode_invalid() {
+		assert!(serde_urlencoded::from_str::<HubRequest>("hub.mode=renew").is_err());
+		assert!(serde_urlencoded::from_str::<HubRequest>("hub.mode=publish").is_err());
+		assert!(
+			serde_urlencoded::from_str::<HubRequest>(
+				"hub.mode=subscribe&hub.topic=a&hub.callback=b&hub.lease_seconds=soon"
+			)
+			.is_err()
+		);
+	}
+
+	#[test]
+	fn lease_is_clamped() {
+		assert_eq!(lease(None), DEFAULT_LEASE);
+		assert_eq!(
+			lease(Some(Duration::from_secs(60))),
+			Duration::from_secs(60)
+		);
+		assert_eq!(lease(Some(MAX_LEASE * 2)), MAX_LEASE);
+	}
+
+	#[test]
+	fn secret_length() {
+		let topic = "https://rssflow.example.com/flow/news";
+
+		let secret = "a".repeat(MAX_SECRET_LENGTH - 1);
+		assert!(check(&subscribe(topic, Some(secret)), None).is_ok());
+
+		let secret = "a".repeat(MAX_SECRET_LENGTH);
+		let err = check(&subscribe(topic, Some(secret)), None).unwrap_err();
+		assert_eq!(err.0, StatusCode::BAD_REQUEST);
+	}
+
+	#[test]
+	fn topic_prefix() {
+		let prefix = Url::parse("https://rssflow.example.com/flow/").unwrap();
+
+		assert!(accepts_topic(None, "https://example.org/feed"));
+		assert!(accepts_topic(
+			Some(&prefix),
+			"https://rssflow.example.com/flow/news"
+		));
+		assert!(!accepts_topic(Some(&prefix), "https://example.org/feed"));
+		assert!(!accepts_topic(
+			Some(&prefix),
+			"https://rssflow.example.com/api/flow/news"
+		));
+
+		let err = check(&subscribe("https://example.org/feed", None), Some(&prefix)).unwrap_err();
+		assert_eq!(err.0, StatusCode::NOT_FOUND);
+		let unsubscribe = HubRequest::Unsubscribe {
+			topic: "https://example.org/feed".to_string(),
+			callback: "https://reader.example.org/callback".to_string(),
+		};
+		assert_eq!(
+			check(&unsubscribe, Some(&prefix)).unwrap_err().0,
+			StatusCode::NOT_FOUND
+		);
+		let publish = |url: &str| HubRequest::Publish {
+			url: url.to_string(),
+		};
+		assert_eq!(
+			check(&publish("https://example.org/feed"), Some(&prefix))
+				.unwrap_err()
+				.0,
+			StatusCode::NOT_FOUND
+		);
+		assert!(
+			check(
+				&publish("https://rssflow.example.com/flow/news"),
+				Some(&prefix)
+			)
+			.is_ok()
+		);
+	}
+
+	#[test]
+	fn publish_requires_prefix() {
+		let publish = HubRequest::Publish {
+			url: "http://169.254.169.254/latest/meta-data/".to_string(),
+		};
+		assert_eq!(check(&publish, None).unwrap_err().0, StatusCode::FORBIDDEN);
+	}
+}
@@ -1,86 +1,162 @@
-use std::{str::FromStr, sync::Arc};
+use std::{
+	collections::{BTreeSet, HashSet},
+	time::SystemTime,
+};
 
 use axum::{
 	Extension, Router,
 	body::Bytes,
 	extract::{Path, Query, State},
-	http::{HeaderMap, StatusCode},
+	http::{HeaderMap, StatusCode, header::CONTENT_TYPE},
 	response::IntoResponse,
 	routing::{get, post},
 };
 use rssflow_service::{
 	NodeExt,
-	proto::{node::ProcessRequest, websub::WebSubEvent},
+	config::config,
+	proto::{
+		node::{NodeMeta, ProcessRequest},
+		registry::RunFlowsRequest,
+		websub::WebSubEvent,
+	},
+	registry_client,
 };
 use sqlx::{PgPool, types::chrono::Utc};
-use tracing::{info, instrument};
+use tracing::{error, info, instrument, warn};
 use uuid::Uuid;
 
 use crate::{
-	Subscription, WebSubSVC,
-	ws::{Verification, X_HUB_SIGNATURE, XHubSignature},
+	WebSubSVC,
+	delivery::{self, Rejection},
+	publish,
+	ws::Verification,
 };
 
-async fn send_to_listeners(sub: &Subscription, body: Bytes) {
-	let mut options = prost_types::Struct::default();
-	options
-		.fields
-		.insert("url".to_string(), sub.web_sub.topic.clone().into());
-	options
-		.fields
-		.insert("hub".to_string(), sub.web_sub.hub.clone().into());
+/// Delivers a push to the nodes that subscribed to it, then re-runs their flows.
+async fn send_to_listeners(pool: &PgPool, uuid: Uuid, event: WebSubEvent) -> anyhow::Result<()> {
+	let listeners = sqlx::query!(
+		"SELECT node_name, address, flow, url FROM listener WHERE subscription = $1",
+		uuid
+	)
+	.fetch_all(pool)
+	.await?;
+
+	let topic = event.topic.clone();
+	let hub = event.hub.clone();
+	let payload: prost_types::Any = event.into();
 
-	let payload: prost_types::Any = WebSubEvent {
-		body: body.to_vec(),
-	}
-	.into();
+	// Nodes cache by the URL they fetch, which may differ from the topic the feed advertises.
+	let deliveries: HashSet<(NodeMeta, String)> = listeners
+		.iter()
+		.map(|l| {
+			let node = NodeMeta {
+				node_name: l.node_name.clone(),
+				address: l.address.clone(),
+			};
+			let url = if l.url.is_empty() {
+				topic.clone()
+			} else {
+				l.url.clone()
+			};
+			(node, url)
+		})
+		.collect();
+	for (node, url) in deliveries {
+		let mut options = prost_types::Struct::default();
+		options.fields.insert("url".to_string(), url.into());
+		options.fields.insert("hub".to_string(), hub.clone().into());
 
-	for node in &sub.nodes {
-		let _ = node
+		let result = node
 			.process(ProcessRequest {
 				payload: Some(payload.clone()),
-				options: Some(options.clone()),
+				options: Some(options),
+				..ProcessRequest::default()
 			})
 			.await;
+		if let Err(err) = result {
+			warn!(
+				"Delivering push for `{topic}` to {} node at {} failed: {err:#}",
+				node.node_name, node.address
+			);
+		}
 	}
+
+	let flows: BTreeSet<String> = listeners.into_iter().map(|l| l.flow).collect();
+	if !flows.is_empty() {
+		registry_client(config::<WebSubSVC>())?
+			.run_flows(RunFlowsRequest {
+				flows: flows.into_iter().collect(),
+			})
+			.await?;
+	}
+	Ok(())
 }
 
 #[instrument(skip_all)]
 pub async fn receive(
 	Path(uuid): Path<Uuid>,
+	State(svc): State<WebSubSVC>,
 	Extension(pool): Extension<PgPool>,
 	headers: HeaderMap,
 	body: Bytes,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
 	let mut conn = pool.acquire().await.map_err(internal_error)?;
-	let record = sqlx::query!(
-		"SELECT secret, topic, hub FROM subscription WHERE uuid = $1",
+	let Some(record) = sqlx::query!(
+		"SELECT secret, topic, hub, require_signature, algorithms FROM subscription WHERE uuid = $1",
 		uuid
 	)
 	.fetch_optional(&mut *conn)
 	.await
-	.map_err(internal_error)?;
-
-	if let Some(record) = record {
-		let signature = headers.get(X_HUB_SIGNATURE);
+	.map_err(internal_error)?
+	else {
+		return Ok(StatusCode::OK);
+	};
 
-		let Some(signature) = signature
-			.and_then(|v| v.to_str().ok())
-			.and_then(|s| XHubSignature::from_str(s).ok())
-		else {
-			return Ok(StatusCode::OK);
-		};
+	let accepted = delivery::verify(
+		&headers,
+		&body,
+		&record.secret,
+		record.require_signature,
+		&record.algorithms,
+	)
+	.and_then(|()| {
+		let window = delivery::delivery_config().dedup_window;
+		if svc.seen.check(uuid, &body, window) {
+			Err(Rejection::Duplicate)
+		} else {
+			Ok(())
+		}
+	});
+	// Rejected pushes are still acknowledged, as the spec requires.
+	if let Err(rejection) = accepted {
+		warn!("Dropped WebSub push for `{}`: {rejection}", record.topic);
+		rejection.record();
+		return Ok(StatusCode::OK);
+	}
 
-		let verified = signature
-			.verify(record.secret.as_bytes(), &body)
-			.map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
+	info!("Received WebSub push for `{}`", record.topic);
 
-		if verified {
-			info!("Received WebSub push for `{}`", record.topic);
+	let event = WebSubEvent {
+		body: body.to_vec(),
+		topic: record.topic,
+		hub: record.hub,
+		content_type: headers
+			.get(CONTENT_TYPE)
+			.and_then(|v| v.to_str().ok())
+			.unwrap_or_default()
+			.to_string(),
+		received_at: Some(SystemTime::now().into()),
+	};
+	// Fails only without receivers.
+	let _ = svc.events.send(event.clone());
 
-			// send_to_listeners(&record, body).await;
+	// Hubs expect a quick answer, the flows run in the background.
+	tokio::spawn(async move {
+		let topic = event.topic.clone();
+		if let Err(err) = send_to_listeners(&pool, uuid, event).await {
+			error!("Handling push for `{topic}` failed: {err:#}");
 		}
-	}
+	});
 
 	Ok(StatusCode::OK)
 }
@@ -106,29 +182,28 @@ pub async fn verify(
 				challenge,
 				lease_seconds,
 			} => {
+				if !record.subscribed || !topic.eq(&record.topic) {
+					return Err((StatusCode::BAD_REQUEST, "Bad request".to_string()));
+				}
+
 				let lease_end = Utc::now() + lease_seconds;
 				sqlx::query!(
-					"UPDATE subscription SET lease_end = $1 WHERE topic = $2",
+					"UPDATE subscription SET lease_end = $1 WHERE uuid = $2",
 					lease_end,
-					record.topic
+					uuid
 				)
 				.execute(&mut *conn)
 				.await
 				.map_err(internal_error)?;
 
 				info!("Verified subscription: `{topic}`");
-
-				if record.subscribed && topic.eq(&record.topic) {
-					Ok((StatusCode::OK, challenge))
-				} else {
-					Err((StatusCode::BAD_REQUEST, "Bad request".to_string()))
-				}
+				Ok((StatusCode::OK, challenge))
 			}
 			Verification::Unsubscribe { topic, challenge } => {
 				if !record.subscribed && topic.eq(&record.topic) {
 					info!("Unsubscribed from `{}`", record.topic);
 
-					sqlx::query!("DELETE FROM subscription WHERE topic = $1", record.topic)
+					sqlx::query!("DELETE FROM subscription WHERE uuid = $1", uuid)
 						.execute(&mut *conn)
 						.await
 						.map_err(internal_error)?;
@@ -147,6 +222,7 @@ pub fn app(state: WebSubSVC) -> Router {
 	Router::new()
 		.route("/websub/{uuid}", post(receive))
 		.route("/websub/{uuid}", get(verify))
+		.route("/websub/hub", post(publish::hub))
 		.route("/websub/check", get(|| async {}))
 		.with_state(state)
 }
@@ -157,3 +233,85 @@ where
 {
 	(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use axum::{
+		Extension,
+		extract::{Path, Query},
+		http::StatusCode,
+	};
+	use sqlx::{
+		PgPool,
+		types::chrono::{DateTime, Utc},
+	};
+	use uuid::Uuid;
+
+	use super::verify;
+	use crate::ws::Verification;
+
+	const TOPIC: &str = "https://example.org/feed.atom";
+
+	async fn subscription(pool: &PgPool, subscribed: bool) -> Uuid {
+		sqlx::query_scalar(
+			"INSERT INTO subscription (topic, hub, secret, subscribed) VALUES ($1, 'https://hub.example.org/', 'secret', $2) RETURNING uuid",
+		)
+		.bind(TOPIC)
+		.bind(subscribed)
+		.fetch_one(pool)
+		.await
+		.unwrap()
+	}
+
+	async fn lease_end(pool: &PgPool) -> Option<DateTime<Utc>> {
+		sqlx::query_scalar("SELECT lease_end FROM subscription")
+			.fetch_one(pool)
+			.await
+			.unwrap()
+	}
+
+	fn subscribe(topic: &str) -> Query<Verification> {
+		Query(Verification::Subscribe {
+			topic: topic.to_string(),
+			challenge: "challenge".to_string(),
+			lease_seconds: Duration::from_secs(3600),
+		})
+	}
+
+	#[sqlx::test]
+	async fn rejected_subscription_keeps_lease(pool: PgPool) {
+		let uuid = subscription(&pool, false).await;
+		let result = verify(Path(uuid), Extension(pool.clone()), subscribe(TOPIC)).await;
+		assert_eq!(
+			result.err().map(|(status, _)| status),
+			Some(StatusCode::BAD_REQUEST)
+		);
+		assert_eq!(lease_end(&pool).await, None);
+	}
+
+	#[sqlx::test]
+	async fn wrong_topic_keeps_lease(pool: PgPool) {
+		let uuid = subscription(&pool, true).await;
+		let result = verify(
+			Path(uuid),
+			Extension(pool.clone()),
+			subscribe("https://example.org/other.atom"),
+		)
+		.await;
+		assert_eq!(
+			result.err().map(|(status, _)| status),
+			Some(StatusCode::BAD_REQUEST)
+		);
+		assert_eq!(lease_end(&pool).await, None);
+	}
+
+	#[sqlx::test]
+	async fn verified_subscription_extends_lease(pool: PgPool) {
+		let uuid = subscription(&pool, true).await;
+		let result = verify(Path(uuid), Extension(pool.clone()), subscribe(TOPIC)).await;
+		assert!(result.is_ok());
+		assert!(lease_end(&pool).await.is_some_and(|end| end > Utc::now()));
+	}
+}
@@ -1,127 +1,192 @@
-use std::array::IntoIter;
+use std::{collections::HashSet, pin::Pin};
 
 use rssflow_service::proto::websub::{
 	SubscribeRequest, SubscribeResponse, WebSubEvent, WebSubRequest,
 	web_sub_service_server::WebSubService,
 };
-use runesys::{Service, config::config};
-use sqlx::PgPool;
+use sqlx::types::chrono::Utc;
+use tokio_stream::{
+	Stream, StreamExt,
+	wrappers::{BroadcastStream, errors::BroadcastStreamRecvError},
+};
 use tonic::{Request, Response, Status};
-use tracing::instrument;
-use uuid::Uuid;
+use tracing::{instrument, warn};
 
-use crate::{WebSubSVC, ws::generate_hmac_secret};
+use crate::{
+	WebSubSVC, delivery,
+	error::Error,
+	hub::{self, Mode},
+	ws::generate_hmac_secret,
+};
 
-#[tonic::async_trait]
-impl WebSubService for WebSubSVC {
-	#[instrument(skip_all)]
-	async fn subscribe(
+impl WebSubSVC {
+	/// Adds the node as a listener, subscribing at the hub when needed.
+	async fn add_listener(
 		&self,
-		request: Request<SubscribeRequest>,
-	) -> Result<Response<SubscribeResponse>, Status> {
-		runesys::telemetry::propagation::accept_trace(&request);
-		let mut conn = request
-			.extensions()
-			.get::<PgPool>()
-			.expect("pg pool")
-			.acquire()
-			.await
-			.expect("");
-
-		let request = request.into_inner();
-		let Some(sub) = request.sub else {
-			return Err(Status::invalid_argument("missing sub"));
-		};
-		let Some(node) = request.node else { todo!() };
-
-		let public_url = rssflow_service::config::config::<WebSubSVC>()
-			.public_url
-			.as_ref();
-		let Some(public_url) = public_url else {
-			return Err(Status::internal("Public url unset"));
-		};
+		mut request: SubscribeRequest,
+	) -> Result<SubscribeResponse, Error> {
+		let sub = request.sub.ok_or(Error::MissingSub)?;
+		let node = request.node.ok_or(Error::MissingNode)?;
+		let mut conn = self.pool.get().ok_or(Error::NoDatabase)?.acquire().await?;
 
 		let record = sqlx::query!(
-			r#"SELECT uuid, secret, lease_end FROM subscription WHERE topic = $1 AND hub = $2"#,
+			"SELECT uuid, secret, lease_end, subscribed, require_signature, algorithms FROM subscription WHERE topic = $1 AND hub = $2",
 			sub.topic,
 			sub.hub
 		)
 		.fetch_optional(&mut *conn)
-		.await
-		.map_err(|e| Status::internal(e.to_string()))?;
+		.await?;
 		let new_subscription = record.is_none();
 
-		let secret: &String = if let Some(r) = &record {
-			&r.secret
-		} else {
-			&generate_hmac_secret()
-		};
+		// Only new, unsubscribed or lapsed subscriptions need the hub.
+		let (uuid, secret, request_hub) = if let Some(record) = record {
+			if !record.subscribed {
+				sqlx::query!(
+					"UPDATE subscription SET subscribed = $2 WHERE uuid = $1",
+					record.uuid,
+					true
+				)
+				.execute(&mut *conn)
+				.await?;
+			}
+
+			// Listeners share the subscription, so a new one may only tighten its policy.
+			if let Some(policy) = request.policy.take() {
+				let current = (record.require_signature, record.algorithms.clone());
+				let (require_signature, algorithms) =
+					delivery::strictest(current, delivery::resolve(Some(policy)))
+						.ok_or_else(|| Error::PolicyConflict(record.algorithms.join(", ")))?;
+				if require_signature != record.require_signature || algorithms != record.algorithms
+				{
+					sqlx::query!(
+						"UPDATE subscription SET require_signature = $2, algorithms = $3 WHERE uuid = $1",
+						record.uuid,
+						require_signature,
+						&algorithms,
+					)
+					.execute(&mut *conn)
+					.await?;
+				}
+			}
 
-		let uuid: &Uuid = if let Some(record) = &record {
-			&record.uuid
+			let lapsed = record.lease_end.is_none_or(|end| end < Utc::now());
+			(record.uuid, record.secret, !record.subscribed || lapsed)
 		} else {
 			tracing::info!("Subscribed to `{}` at `{}`", sub.topic, sub.hub);
 
-			&sqlx::query_scalar!(
-				"INSERT INTO subscription (topic, hub, secret) VALUES ($1, $2, $3) RETURNING uuid",
+			let secret = generate_hmac_secret();
+			let (require_signature, algorithms) = delivery::resolve(request.policy.take());
+			let uuid = sqlx::query_scalar!(
+				"INSERT INTO subscription (topic, hub, secret, require_signature, algorithms) VALUES ($1, $2, $3, $4, $5) RETURNING uuid",
 				sub.topic,
 				sub.hub,
 				secret,
+				require_signature,
+				&algorithms,
 			)
 			.fetch_one(&mut *conn)
-			.await
-			.map_err(|e| Status::internal(e.to_string()))?
+			.await?;
+			(uuid, secret, true)
 		};
 
-		let callback = format!("{public_url}websub/{uuid}");
-		let rb = reqwest::Client::new().post(&sub.hub).form(&[
-			("hub.callback", callback.as_str()),
-			("hub.mode", "subscribe"),
-			("hub.topic", &sub.topic),
-			("hub.secret", secret.as_str()),
-		]);
+		sqlx::query!(
+			"INSERT INTO listener (subscription, node_name, address, flow, url) VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING",
+			uuid,
+			node.node_name,
+			node.address,
+			request.flow,
+			request.url,
+		)
+		.execute(&mut *conn)
+		.await?;
 
-		let resp = rb
-			.send()
-			.await
-			.map_err(|e| Status::unavailable(e.to_string()))?;
-		tracing::info!("Response: {}", resp.status());
-		resp.error_for_status()
-			.map_err(|e| Status::unavailable(e.to_string()))?;
+		if request_hub {
+			hub::request(&sub, uuid, &secret, Mode::Subscribe)
+				.await
+				.map_err(Error::Hub)?;
+		}
 
-		Ok(SubscribeResponse { new_subscription }.into())
+		Ok(SubscribeResponse { new_subscription })
 	}
 
+	/// Removes the node as a listener, unsubscribing at the hub once nobody listens.
+	async fn remove_listener(&self, request: SubscribeRequest) -> Result<SubscribeResponse, Error> {
+		let sub = request.sub.ok_or(Error::MissingSub)?;
+		let node = request.node.ok_or(Error::MissingNode)?;
+		let mut conn = self.pool.get().ok_or(Error::NoDatabase)?.acquire().await?;
+
+		let Some(record) = sqlx::query!(
+			"SELECT uuid, secret, lease_end, subscribed FROM subscription WHERE topic = $1 AND hub = $2",
+			sub.topic,
+			sub.hub
+		)
+		.fetch_optional(&mut *conn)
+		.await?
+		else {
+			return Err(Error::NotFound);
+		};
+
+		sqlx::query!(
+			"DELETE FROM listener WHERE subscription = $1 AND node_name = $2 AND address = $3 AND flow = $4",
+			record.uuid,
+			node.node_name,
+			node.address,
+			request.flow,
+		)
+		.execute(&mut *conn)
+		.await?;
+
+		let listened = sqlx::query_scalar!(
+			"SELECT EXISTS(SELECT 1 FROM listener WHERE subscription = $1)",
+			record.uuid
+		)
+		.fetch_one(&mut *conn)
+		.await?
+		.unwrap_or_default();
+
+		// The row stays until the hub verifies the unsubscription.
+		if !listened && record.subscribed {
+			sqlx::query!(
+				"UPDATE subscription SET subscribed = $2 WHERE uuid = $1",
+				record.uuid,
+				false
+			)
+			.execute(&mut *conn)
+			.await?;
+
+			tracing::info!("Unsubscribing from `{}` at `{}`", sub.topic, sub.hub);
+			hub::request(&sub, record.uuid, &record.secret, Mode::Unsubscribe)
+				.await
+				.map_err(Error::Hub)?;
+		}
+
+		Ok(SubscribeResponse {
+			new_subscription: false,
+		})
+	}
+}
+
+#[tonic::async_trait]
+impl WebSubService for WebSubSVC {
 	#[instrument(skip_all)]
-	async fn unsubscribe(
+	async fn subscribe(
 		&self,
 		request: Request<SubscribeRequest>,
 	) -> Result<Response<SubscribeResponse>, Status> {
 		runesys::telemetry::propagation::accept_trace(&request);
-		let request = request.into_inner();
-		let Some(sub) = request.sub else {
-			return Err(Status::invalid_argument("missing sub"));
-		};
-		let Some(node) = request.node else { todo!() };
-
-		let uuid = self.ws.lock().unwrap().get(&sub).copied();
-		if let Some(uuid) = uuid {
-			self.subscriptions
-				.lock()
-				.unwrap()
-				.get_mut(&uuid)
-				.unwrap()
-				.nodes
-				.retain(|e| !e.eq(&node));
-		}
+		Ok(self.add_listener(request.into_inner()).await?.into())
+	}
 
-		Ok(SubscribeResponse {
-			new_subscription: false,
-		}
-		.into())
+	#[instrument(skip_all)]
+	async fn unsubscribe(
+		&self,
+		request: Request<SubscribeRequest>,
+	) -> Result<Response<SubscribeResponse>, Status> {
+		runesys::telemetry::propagation::accept_trace(&request);
+		Ok(self.remove_listener(request.into_inner()).await?.into())
 	}
 
-	type ReceiveStream = tokio_stream::Iter<IntoIter<Result<WebSubEvent, Status>, 1>>;
+	type ReceiveStream = Pin<Box<dyn Stream<Item = Result<WebSubEvent, Status>> + Send>>;
 
 	#[instrument(skip_all)]
 	async fn receive(
@@ -129,8 +194,209 @@ impl WebSubService for WebSubSVC {
 		request: Request<WebSubRequest>,
 	) -> Result<Response<Self::ReceiveStream>, Status> {
 		runesys::telemetry::propagation::accept_trace(&request);
-		let stream = tokio_stream::iter([Ok(WebSubEvent { body: Vec::new() })]);
+		let topics: HashSet<String> = request.into_inner().topics.into_iter().collect();
 
-		Ok(stream.into())
+		let stream =
+			BroadcastStream::new(self.events.subscribe()).filter_map(move |event| match event {
+				Ok(event) if topics.is_empty() || topics.contains(&event.topic) => Some(Ok(event)),
+				Ok(_) => None,
+				Err(BroadcastStreamRecvError::Lagged(missed)) => {
+					warn!("Receive stream fell behind, {missed} pushes missed");
+					None
+				}
+			});
+
+		Ok(Response::new(Box::pin(stream)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rssflow_service::proto::{
+		node::NodeMeta,
+		websub::{SignaturePolicy, WebSub},
+	};
+	use sqlx::PgPool;
+	use tonic::{Code, Status};
+
+	use super::SubscribeRequest;
+	use crate::{WebSubSVC, error::Error};
+
+	fn svc(pool: PgPool) -> WebSubSVC {
+		let svc = WebSubSVC::default();
+		svc.pool.set(pool).unwrap();
+		svc
+	}
+
+	fn request() -> SubscribeRequest {
+		SubscribeRequest {
+			sub: Some(WebSub {
+				// Tests have no public url configured, so hub requests fail before reaching the hub.
+				hub: "http://127.0.0.1:9/".to_string(),
+				topic: "https://example.org/feed.atom".to_string(),
+			}),
+			node: Some(NodeMeta {
+				address: "http://fetch:50051".to_string(),
+				node_name: "Fetch".to_string(),
+			}),
+			flow: "example".to_string(),
+			policy: None,
+			url: "https://example.org/feed".to_string(),
+		}
+	}
+
+	fn code(err: Error) -> Code {
+		Status::from(err).code()
+	}
+
+	#[tokio::test]
+	async fn missing_sub() {
+		let request = SubscribeRequest {
+			sub: None,
+			..request()
+		};
+
+		let err = WebSubSVC::default()
+			.add_listener(request.clone())
+			.await
+			.unwrap_err();
+		assert!(matches!(err, Error::MissingSub));
+		assert_eq!(code(err), Code::InvalidArgument);
+
+		let err = WebSubSVC::default()
+			.remove_listener(request)
+			.await
+			.unwrap_err();
+		assert!(matches!(err, Error::MissingSub));
+	}
+
+	#[tokio::test]
+	async fn missing_node() {
+		let request = SubscribeRequest {
+			node: None,
+			..request()
+		};
+
+		let err = WebSubSVC::default()
+			.add_listener(request.clone())
+			.await
+			.unwrap_err();
+		assert!(matches!(err, Error::MissingNode));
+		assert_eq!(code(err), Code::InvalidArgument);
+
+		let err = WebSubSVC::default()
+			.remove_listener(request)
+			.await
+			.unwrap_err();
+		assert!(matches!(err, Error::MissingNode));
+	}
+
+	#[tokio::test]
+	async fn no_database() {
+		let err = WebSubSVC::default()
+			.add_listener(request())
+			.await
+			.unwrap_err();
+		assert!(matches!(err, Error::NoDatabase));
+		assert_eq!(code(err), Code::Unavailable);
+	}
+
+	#[sqlx::test]
+	async fn closed_pool(pool: PgPool) {
+		pool.close().await;
+
+		let err = svc(pool).add_listener(request()).await.unwrap_err();
+		assert!(matches!(err, Error::Database(sqlx::Error::PoolClosed)));
+		assert_eq!(code(err), Code::Unavailable);
+	}
+
+	#[test]
+	fn query_error() {
+		assert_eq!(
+			code(Error::Database(sqlx::Error::RowNotFound)),
+			Code::Internal
+		);
+	}
+
+	#[sqlx::test]
+	async fn unknown_subscription(pool: PgPool) {
+		let err = svc(pool).remove_listener(request()).await.unwrap_err();
+		assert!(matches!(err, Error::NotFound));
+		assert_eq!(code(err), Code::NotFound);
+	}
+
+	#[sqlx::test]
+	async fn hub_request_failed(pool: PgPool) {
+		let svc = svc(pool.clone());
+
+		let err = svc.add_listener(request()).await.unwrap_err();
+		assert!(matches!(err, Error::Hub(_)));
+		assert_eq!(code(err), Code::Unavailable);
+
+		// The listener is kept, so reconciling requests the subscription again.
+		let listeners: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM listener")
+			.fetch_one(&pool)
+			.await
+			.unwrap();
+		assert_eq!(listeners, 1);
+
+		let err = svc.remove_listener(request()).await.unwrap_err();
+		assert!(matches!(err, Error::Hub(_)));
+	}
+
+	#[sqlx::test]
+	async fn policy_only_tightens(pool: PgPool) {
+		let svc = svc(pool.clone());
+		let request = |require_signature: bool, algorithms: &[&str]| SubscribeRequest {
+			policy: Some(SignaturePolicy {
+				require_signature,
+				algorithms: algorithms.iter().map(ToString::to_string).collect(),
+			}),
+			..request()
+		};
+
+		// Requesting the hub fails, but the subscription is stored before that.
+		svc.add_listener(request(true, &["sha256", "sha512"]))
+			.await
+			.unwrap_err();
+		svc.add_listener(request(false, &["sha1", "sha256"]))
+			.await
+			.unwrap_err();
+		let policy: (bool, Vec<String>) =
+			sqlx::query_as("SELECT require_signature, algorithms FROM subscription")
+				.fetch_one(&pool)
+				.await
+				.unwrap();
+		assert_eq!(policy, (true, vec!["sha256".to_string()]));
+
+		let err = svc
+			.add_listener(request(false, &["sha1"]))
+			.await
+			.unwrap_err();
+		assert!(matches!(err, Error::PolicyConflict(_)));
+		assert_eq!(code(err), Code::FailedPrecondition);
+	}
+
+	#[sqlx::test]
+	async fn same_topic_other_hub(pool: PgPool) {
+		let svc = svc(pool.clone());
+		let other_hub = SubscribeRequest {
+			sub: Some(WebSub {
+				hub: "http://127.0.0.2:9/".to_string(),
+				..request().sub.unwrap()
+			}),
+			..request()
+		};
+
+		for request in [request(), other_hub] {
+			let err = svc.add_listener(request).await.unwrap_err();
+			assert!(matches!(err, Error::Hub(_)), "{err}");
+		}
+
+		let subscriptions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM subscription")
+			.fetch_one(&pool)
+			.await
+			.unwrap();
+		assert_eq!(subscriptions, 2);
 	}
 }
@@ -14,6 +14,8 @@ pub const HMAC_SECRET_LENGTH: usize = 64; // 64 bytes = 512 bits
 
 #[allow(clippy::declare_interior_mutable_const)]
 pub const X_HUB_SIGNATURE: HeaderName = HeaderName::from_static("x-hub-signature");
+#[allow(clippy::declare_interior_mutable_const)]
+pub const X_HUB_SIGNATURE_256: HeaderName = HeaderName::from_static("x-hub-signature-256");
 
 pub fn generate_hmac_secret() -> String {
 	let mut bytes = [0u8; HMAC_SECRET_LENGTH];
@@ -49,16 +51,14 @@ pub struct XHubSignature {
 }
 
 impl XHubSignature {
+	pub fn method(&self) -> &str {
+		&self.method
+	}
+
 	#[tracing::instrument(skip(secret, message))]
 	pub fn verify(&self, secret: &[u8], message: &[u8]) -> anyhow::Result<bool> {
 		Ok(match self.method.as_str() {
-			#[cfg(feature = "sha1")]
 			"sha1" => mac::verify_hmac::<sha1::Sha1>(&self.signature, secret, message)?,
-			#[cfg(not(feature = "sha1"))]
-			"sha1" => {
-				tracing::error!("Unsupported sha1 signature on WebSub push");
-				false
-			}
 			"sha256" => mac::verify_hmac::<Sha256>(&self.signature, secret, message)?,
 			"sha384" => mac::verify_hmac::<Sha384>(&self.signature, secret, message)?,
 			"sha512" => mac::verify_hmac::<Sha512>(&self.signature, secret, message)?,
@@ -113,4 +113,21 @@ pub mod mac {
 		hmac.update(message);
 		Ok(hmac.verify_slice(signature).is_ok())
 	}
+
+	pub fn sign_hmac<D>(secret: &[u8], message: &[u8]) -> anyhow::Result<Vec<u8>>
+	where
+		D: CoreProxy,
+		D::Core: HashMarker
+			+ UpdateCore
+			+ FixedOutputCore
+			+ BufferKindUser<BufferKind = Eager>
+			+ Default
+			+ Clone,
+		<D::Core as BlockSizeUser>::BlockSize: IsLess<U256>,
+		Le<<D::Core as BlockSizeUser>::BlockSize, U256>: NonZero,
+	{
+		let mut hmac: Hmac<D> = Hmac::new_from_slice(secret)?;
+		hmac.update(message);
+		Ok(hmac.finalize().into_bytes().to_vec())
+	}
 }
@@ -1,7 +1,12 @@
 use chrono::{DateTime, FixedOffset, Utc};
 use prost_types::Timestamp;
 
-use crate::{impl_name, node::Field};
+use crate::impl_name;
+
+pub mod json;
+pub mod path;
+#[cfg(feature = "rss")]
+mod rss;
 
 tonic::include_proto!("rssflow.feed");
 
@@ -13,41 +18,18 @@ impl_name!(Link, "rssflow.feed");
 
 impl_name!(StringValue, "rssflow.feed");
 
-impl Entry {
-	#[must_use]
-	pub fn value(&self, field: Field) -> Option<&String> {
-		match field {
-			Field::Author => {
-				// self.authors.first().map(|p| &p.name)
-				None
-			}
-			Field::Summary => self.summary.as_ref().map(|t| &t.value),
-			Field::Content => self.content.as_ref().map(|c| &c.value),
-			Field::Title => Some(&self.title),
-		}
-	}
-
-	pub fn value_mut(&mut self, field: Field) -> Option<&mut String> {
-		match field {
-			Field::Author => {
-				// self.authors.first().map(|p| &p.name)
-				None
-			}
-			Field::Summary => self.summary.as_mut().map(|t| &mut t.value),
-			Field::Content => self.content.as_mut().map(|c| &mut c.value),
-			Field::Title => Some(&mut self.title),
-		}
-	}
-}
-
 #[cfg(feature = "atom")]
 mod atom {
 	use atom_syndication::{
-		Content as AtomContent, Entry as AtomEntry, Feed as AtomFeed, Link as AtomLink,
-		Person as AtomPerson, Text as AtomText, TextType as AtomTextType,
+		Category as AtomCategory, Content as AtomContent, Entry as AtomEntry, Feed as AtomFeed,
+		Generator as AtomGenerator, Link as AtomLink, Person as AtomPerson, Text as AtomText,
+		TextType as AtomTextType,
 	};
 
-	use super::{Content, Entry, Feed, Link, Person, Text, TextType, from_timestamp, to_timestamp};
+	use super::{
+		Category, Content, Entry, Feed, Generator, Link, Person, Text, TextType, from_timestamp,
+		non_empty, to_timestamp,
+	};
 
 	/// Converts an Atom feed into a protobuf `Feed`
 	impl From<&AtomFeed> for Feed {
@@ -58,6 +40,15 @@ mod atom {
 				updated: Some(to_timestamp(feed.updated())),
 				authors: feed.authors.iter().map(Into::into).collect(),
 				entries: feed.entries.iter().map(Into::into).collect(),
+				links: feed.links.iter().map(Into::into).collect(),
+				subtitle: feed.subtitle.as_ref().map(Into::into),
+				icon: feed.icon.clone().unwrap_or_default(),
+				logo: feed.logo.clone().unwrap_or_default(),
+				generator: feed.generator.as_ref().map(Into::into),
+				rights: feed.rights.as_ref().map(Into::into),
+				contributors: feed.contributors.iter().map(Into::into).collect(),
+				categories: feed.categories.iter().map(Into::into).collect(),
+				lang: feed.lang.clone().unwrap_or_default(),
 			}
 		}
 	}
@@ -73,6 +64,11 @@ mod atom {
 				links: entry.links.iter().map(Into::into).collect(),
 				summary: entry.summary.as_ref().map(Into::into),
 				content: entry.content.as_ref().map(Into::into),
+				seen: false,
+				categories: entry.categories.iter().map(Into::into).collect(),
+				published: entry.published.as_ref().map(to_timestamp),
+				contributors: entry.contributors.iter().map(Into::into).collect(),
+				rights: entry.rights.as_ref().map(Into::into),
 			}
 		}
 	}
@@ -121,6 +117,10 @@ mod atom {
 			Link {
 				href: link.href.clone(),
 				rel: link.rel.clone(),
+				media_type: link.mime_type.clone().unwrap_or_default(),
+				length: link.length.clone().unwrap_or_default(),
+				hreflang: link.hreflang.clone().unwrap_or_default(),
+				title: link.title.clone().unwrap_or_default(),
 			}
 		}
 	}
@@ -130,7 +130,50 @@ mod atom {
 			AtomLink {
 				href: link.href,
 				rel: link.rel,
-				..Default::default()
+				hreflang: non_empty(link.hreflang),
+				mime_type: non_empty(link.media_type),
+				title: non_empty(link.title),
+				length: non_empty(link.length),
+			}
+		}
+	}
+
+	impl From<&AtomCategory> for Category {
+		fn from(category: &AtomCategory) -> Self {
+			Category {
+				term: category.term.clone(),
+				scheme: category.scheme.clone().unwrap_or_default(),
+				label: category.label.clone().unwrap_or_default(),
+			}
+		}
+	}
+
+	impl From<Category> for AtomCategory {
+		fn from(category: Category) -> Self {
+			AtomCategory {
+				term: category.term,
+				scheme: non_empty(category.scheme),
+				label: non_empty(category.label),
+			}
+		}
+	}
+
+	impl From<&AtomGenerator> for Generator {
+		fn from(generator: &AtomGenerator) -> Self {
+			Generator {
+				value: generator.value.clone(),
+				uri: generator.uri.clone().unwrap_or_default(),
+				version: generator.version.clone().unwrap_or_default(),
+			}
+		}
+	}
+
+	impl From<Generator> for AtomGenerator {
+		fn from(generator: Generator) -> Self {
+			AtomGenerator {
+				value: generator.value,
+				uri: non_empty(generator.uri),
+				version: non_empty(generator.version),
 			}
 		}
 	}
@@ -142,56 +185,39 @@ mod atom {
 				value: content.value.clone().unwrap_or_default(),
 				lang: content.lang.clone().unwrap_or_default(),
 				content_type: content.content_type.clone().unwrap_or_default(),
+				src: content.src.clone().unwrap_or_default(),
 			}
 		}
 	}
 	impl From<Content> for AtomContent {
 		fn from(value: Content) -> Self {
 			AtomContent {
-				value: if value.value.is_empty() {
-					None
-				} else {
-					Some(value.value)
-				},
-				lang: if value.lang.is_empty() {
-					None
-				} else {
-					Some(value.lang)
-				},
-				content_type: if value.content_type.is_empty() {
-					None
-				} else {
-					Some(value.content_type)
-				},
+				value: non_empty(value.value),
+				lang: non_empty(value.lang),
+				content_type: non_empty(value.content_type),
+				src: non_empty(value.src),
 				..AtomContent::default()
 			}
 		}
 	}
 
-	/// Converts an Atom content object into a protobuf `Content`
+	/// Converts an Atom person construct into a protobuf `Person`
 	impl From<&AtomPerson> for Person {
 		fn from(person: &AtomPerson) -> Self {
 			Self {
 				name: person.name.clone(),
 				email: person.email.clone().unwrap_or_default(),
 				uri: person.uri.clone().unwrap_or_default(),
+				..Person::default()
 			}
 		}
 	}
 	impl From<Person> for AtomPerson {
 		fn from(person: Person) -> Self {
 			Self {
-				name: person.name.clone(),
-				email: if person.email.is_empty() {
-					None
-				} else {
-					Some(person.email.clone())
-				},
-				uri: if person.uri.is_empty() {
-					None
-				} else {
-					Some(person.uri.clone())
-				},
+				name: person.name,
+				email: non_empty(person.email),
+				uri: non_empty(person.uri),
 			}
 		}
 	}
@@ -207,7 +233,12 @@ mod atom {
 					.and_then(from_timestamp)
 					.map(Into::into)
 					.unwrap_or_default(),
+				published: value.published.and_then(from_timestamp).map(Into::into),
+				authors: value.authors.into_iter().map(Into::into).collect(),
+				contributors: value.contributors.into_iter().map(Into::into).collect(),
 				links: value.links.into_iter().map(Into::into).collect(),
+				categories: value.categories.into_iter().map(Into::into).collect(),
+				rights: value.rights.map(Into::into),
 				summary: value.summary.map(Into::into),
 				content: value.content.map(Into::into),
 
@@ -226,11 +257,95 @@ mod atom {
 					.and_then(from_timestamp)
 					.map(Into::into)
 					.unwrap_or_default(),
+				authors: value.authors.into_iter().map(Into::into).collect(),
+				contributors: value.contributors.into_iter().map(Into::into).collect(),
+				categories: value.categories.into_iter().map(Into::into).collect(),
+				generator: value.generator.map(Into::into),
+				icon: non_empty(value.icon),
+				logo: non_empty(value.logo),
+				rights: value.rights.map(Into::into),
+				subtitle: value.subtitle.map(Into::into),
+				lang: non_empty(value.lang),
+				links: value.links.into_iter().map(Into::into).collect(),
 				entries: value.entries.into_iter().map(Into::into).collect(),
 				..Default::default()
 			}
 		}
 	}
+
+	#[cfg(test)]
+	mod tests {
+		use std::str::FromStr;
+
+		use atom_syndication::Feed as AtomFeed;
+
+		use super::Feed;
+
+		fn round_trip(xml: &str) {
+			let atom = AtomFeed::from_str(xml).unwrap();
+			let feed = Feed::from(&atom);
+			assert_eq!(AtomFeed::from(feed), atom);
+		}
+
+		#[test]
+		fn minimal_feed() {
+			round_trip(
+				r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+	<title>Example Feed</title>
+	<id>urn:uuid:60a76c80-d399-11d9-b93c-0003939e0af6</id>
+	<updated>2003-12-13T18:30:02Z</updated>
+</feed>"#,
+			);
+		}
+
+		#[test]
+		fn full_feed() {
+			round_trip(
+				r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xml:lang="en">
+	<title>Example Podcast</title>
+	<subtitle type="html">A &lt;em&gt;lot&lt;/em&gt; of talking</subtitle>
+	<id>tag:example.org,2003:podcast</id>
+	<updated>2005-07-31T12:29:29+02:00</updated>
+	<link rel="alternate" type="text/html" hreflang="en" href="http://example.org/"/>
+	<link rel="self" type="application/atom+xml" href="http://example.org/feed.atom"/>
+	<link rel="hub" href="https://hub.example.org/"/>
+	<rights>Copyright (c) 2005, Example</rights>
+	<generator uri="http://www.example.com/" version="1.0">Example Toolkit</generator>
+	<icon>http://example.org/favicon.ico</icon>
+	<logo>http://example.org/logo.png</logo>
+	<author>
+		<name>Mark Pilgrim</name>
+		<uri>http://example.org/</uri>
+		<email>f8dy@example.com</email>
+	</author>
+	<contributor><name>Sam Ruby</name></contributor>
+	<category term="technology" scheme="http://example.org/categories" label="Technology"/>
+	<entry>
+		<title>Episode 1</title>
+		<link rel="alternate" type="text/html" href="http://example.org/2005/04/02/atom"/>
+		<link rel="enclosure" type="audio/mpeg" length="1337" title="Audio" href="http://example.org/audio/ep1.mp3"/>
+		<id>tag:example.org,2003:3.2397</id>
+		<updated>2005-07-31T12:29:29Z</updated>
+		<published>2003-12-13T08:29:29-04:00</published>
+		<author><name>Mark Pilgrim</name></author>
+		<contributor><name>Joe Gregorio</name></contributor>
+		<category term="audio"/>
+		<rights type="html">&lt;p&gt;CC BY 4.0&lt;/p&gt;</rights>
+		<summary>The first episode</summary>
+		<content type="html">&lt;p&gt;Show notes&lt;/p&gt;</content>
+	</entry>
+	<entry>
+		<title>Episode 2</title>
+		<id>tag:example.org,2003:3.2398</id>
+		<updated>2005-08-01T12:00:00Z</updated>
+		<content type="audio/mpeg" src="http://example.org/audio/ep2.mp3"/>
+	</entry>
+</feed>"#,
+			);
+		}
+	}
 }
 
 /// Converts a chrono `DateTime<FixedOffset>` into a protobuf `Timestamp`
@@ -244,3 +359,7 @@ fn to_timestamp(dt: &DateTime<FixedOffset>) -> Timestamp {
 fn from_timestamp(t: Timestamp) -> Option<DateTime<Utc>> {
 	DateTime::from_timestamp(t.seconds, t.nanos as u32)
 }
+
+fn non_empty(s: String) -> Option<String> {
+	if s.is_empty() { None } else { Some(s) }
+}
@@ -0,0 +1,381 @@
+//! [JSON Feed 1.1](https://www.jsonfeed.org/version/1.1/) model.
+
+use chrono::DateTime;
+use prost_types::Timestamp;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::{
+	Category, Content, Entry, Feed, Link, Person, Text, TextType, from_timestamp, non_empty,
+	to_timestamp,
+};
+
+pub const VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct JsonFeed {
+	pub version: String,
+	pub title: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub home_page_url: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub feed_url: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub description: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub icon: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub favicon: Option<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub authors: Vec<Author>,
+	/// Deprecated in 1.1 in favour of `authors`, only read.
+	#[serde(default, skip_serializing)]
+	pub author: Option<Author>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub language: Option<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub hubs: Vec<Hub>,
+	pub items: Vec<Item>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Item {
+	/// Must be a string, but JSON Feed 1.0 publishers commonly use numbers.
+	#[serde(deserialize_with = "deserialize_id")]
+	pub id: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub url: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub external_url: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub title: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub content_html: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub content_text: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub summary: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub image: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub date_published: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub date_modified: Option<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub authors: Vec<Author>,
+	/// Deprecated in 1.1 in favour of `authors`, only read.
+	#[serde(default, skip_serializing)]
+	pub author: Option<Author>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub tags: Vec<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub language: Option<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub attachments: Vec<Attachment>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Author {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub name: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub url: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub avatar: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Attachment {
+	pub url: String,
+	pub mime_type: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub title: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub size_in_bytes: Option<u64>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub duration_in_seconds: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Hub {
+	#[serde(rename = "type")]
+	pub r#type: String,
+	pub url: String,
+}
+
+fn deserialize_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+	#[derive(Deserialize)]
+	#[serde(untagged)]
+	enum Id {
+		String(String),
+		Integer(i64),
+		Float(f64),
+	}
+
+	Ok(match Id::deserialize(deserializer)? {
+		Id::String(s) => s,
+		Id::Integer(n) => n.to_string(),
+		Id::Float(n) => n.to_string(),
+	})
+}
+
+fn parse_date(date: &str) -> Option<Timestamp> {
+	DateTime::parse_from_rfc3339(date)
+		.ok()
+		.map(|d| to_timestamp(&d))
+}
+
+fn format_date(t: Option<Timestamp>) -> Option<String> {
+	t.and_then(from_timestamp).map(|d| d.to_rfc3339())
+}
+
+fn link(href: String, rel: &str) -> Link {
+	Link {
+		href,
+		rel: rel.to_string(),
+		..Link::default()
+	}
+}
+
+fn find_link(links: &[Link], rel: &str) -> Option<String> {
+	links
+		.iter()
+		.find(|l| l.rel == rel || (rel == "alternate" && l.rel.is_empty()))
+		.map(|l| l.href.clone())
+}
+
+impl From<Author> for Person {
+	fn from(author: Author) -> Self {
+		let url = author.url.unwrap_or_default();
+		let avatar = author.avatar.unwrap_or_default();
+		match url.strip_prefix("mailto:") {
+			Some(email) => Person {
+				name: author.name.unwrap_or_default(),
+				email: email.to_string(),
+				avatar,
+				..Person::default()
+			},
+			None => Person {
+				name: author.name.unwrap_or_default(),
+				uri: url,
+				avatar,
+				..Person::default()
+			},
+		}
+	}
+}
+
+impl From<Person> for Author {
+	fn from(person: Person) -> Self {
+		Author {
+			name: non_empty(person.name),
+			// JSON Feed has no email field, fall back to a mailto link
+			url: non_empty(person.uri)
+				.or_else(|| non_empty(person.email).map(|email| format!("mailto:{email}"))),
+			avatar: non_empty(person.avatar),
+		}
+	}
+}
+
+impl From<Attachment> for Link {
+	fn from(attachment: Attachment) -> Self {
+		Link {
+			href: attachment.url,
+			rel: "enclosure".to_string(),
+			media_type: attachment.mime_type,
+			length: attachment
+				.size_in_bytes
+				.map(|n| n.to_string())
+				.unwrap_or_default(),
+			title: attachment.title.unwrap_or_default(),
+			..Link::default()
+		}
+	}
+}
+
+impl From<Item> for Entry {
+	fn from(item: Item) -> Self {
+		let published = item.date_published.as_deref().and_then(parse_date);
+
+		let mut links: Vec<Link> = Vec::new();
+		links.extend(item.url.map(|href| link(href, "alternate")));
+		links.extend(item.external_url.map(|href| link(href, "related")));
+		links.extend(item.attachments.into_iter().map(Into::into));
+
+		let content = match (item.content_html, item.content_text) {
+			(Some(value), _) => Some(Content {
+				value,
+				content_type: "html".to_string(),
+				..Content::default()
+			}),
+			(None, Some(value)) => Some(Content {
+				value,
+				content_type: "text".to_string(),
+				..Content::default()
+			}),
+			(None, None) => None,
+		};
+
+		Entry {
+			title: item.title.unwrap_or_default(),
+			id: item.id,
+			updated: item
+				.date_modified
+				.as_deref()
+				.and_then(parse_date)
+				.or(published),
+			published,
+			authors: item
+				.authors
+				.into_iter()
+				.chain(item.author)
+				.map(Into::into)
+				.collect(),
+			links,
+			summary: item.summary.map(|value| Text {
+				value,
+				r#type: TextType::Text as i32,
+			}),
+			content,
+			categories: item
+				.tags
+				.into_iter()
+				.map(|term| Category {
+					term,
+					..Category::default()
+				})
+				.collect(),
+			..Entry::default()
+		}
+	}
+}
+
+impl From<JsonFeed> for Feed {
+	fn from(feed: JsonFeed) -> Self {
+		let entries: Vec<Entry> = feed.items.into_iter().map(Into::into).collect();
+
+		let mut links: Vec<Link> = Vec::new();
+		links.extend(feed.home_page_url.map(|href| link(href, "alternate")));
+		links.extend(feed.feed_url.map(|href| link(href, "self")));
+		links.extend(feed.hubs.into_iter().map(|hub| link(hub.url, "hub")));
+
+		Feed {
+			id: find_link(&links, "self")
+				.or_else(|| find_link(&links, "alternate"))
+				.unwrap_or_else(|| feed.title.clone()),
+			title: feed.title,
+			updated: entries
+				.iter()
+				.filter_map(|e| e.updated)
+				.max_by_key(|t| (t.seconds, t.nanos)),
+			authors: feed
+				.authors
+				.into_iter()
+				.chain(feed.author)
+				.map(Into::into)
+				.collect(),
+			entries,
+			links,
+			subtitle: feed.description.map(|value| Text {
+				value,
+				r#type: TextType::Text as i32,
+			}),
+			icon: feed.favicon.unwrap_or_default(),
+			logo: feed.icon.unwrap_or_default(),
+			lang: feed.language.unwrap_or_default(),
+			..Feed::default()
+		}
+	}
+}
+
+impl From<Entry> for Item {
+	fn from(entry: Entry) -> Self {
+		let (content_html, content_text) = match entry.content {
+			Some(c) if c.content_type == "text" => (None, non_empty(c.value)),
+			Some(c) => (non_empty(c.value), None),
+			None => (None, None),
+		};
+
+		Item {
+			url: find_link(&entry.links, "alternate"),
+			external_url: find_link(&entry.links, "related"),
+			attachments: entry
+				.links
+				.into_iter()
+				.filter(|l| l.rel == "enclosure")
+				.map(|l| Attachment {
+					size_in_bytes: l.length.parse().ok(),
+					url: l.href,
+					mime_type: l.media_type,
+					title: non_empty(l.title),
+					..Attachment::default()
+				})
+				.collect(),
+			id: entry.id,
+			title: Some(entry.title),
+			content_html,
+			content_text,
+			summary: entry.summary.map(|t| t.value),
+			date_published: format_date(entry.published),
+			date_modified: format_date(entry.updated),
+			authors: entry.authors.into_iter().map(Into::into).collect(),
+			tags: entry.categories.into_iter().map(|c| c.term).collect(),
+			..Item::default()
+		}
+	}
+}
+
+impl From<Feed> for JsonFeed {
+	fn from(feed: Feed) -> Self {
+		JsonFeed {
+			version: VERSION.to_string(),
+			title: feed.title,
+			home_page_url: find_link(&feed.links, "alternate"),
+			feed_url: find_link(&feed.links, "self"),
+			hubs: feed
+				.links
+				.iter()
+				.filter(|l| l.rel == "hub")
+				.map(|l| Hub {
+					r#type: "WebSub".to_string(),
+					url: l.href.clone(),
+				})
+				.collect(),
+			description: feed.subtitle.map(|t| t.value),
+			icon: non_empty(feed.logo),
+			favicon: non_empty(feed.icon),
+			language: non_empty(feed.lang),
+			authors: feed.authors.into_iter().map(Into::into).collect(),
+			items: feed.entries.into_iter().map(Into::into).collect(),
+			..JsonFeed::default()
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::{Feed, JsonFeed};
+
+	#[test]
+	fn attachment_round_trip() {
+		let attachment = json!({
+			"url": "https://example.org/ep1.mp3",
+			"mime_type": "audio/mpeg",
+			"title": "Episode 1",
+			"size_in_bytes": 1337
+		});
+		let json: JsonFeed = serde_json::from_value(json!({
+			"version": "https://jsonfeed.org/version/1.1",
+			"title": "Example Podcast",
+			"items": [{ "id": "1", "attachments": [attachment] }]
+		}))
+		.unwrap();
+
+		let feed = Feed::from(json);
+		assert_eq!(feed.entries[0].links[0].title, "Episode 1");
+
+		let json = serde_json::to_value(JsonFeed::from(feed)).unwrap();
+		assert_eq!(json["items"][0]["attachments"], json!([attachment]));
+	}
+}
@@ -0,0 +1,403 @@
+//! Addressing of entry fields by path expressions, e.g. `title`, `content.type`,
+//! `authors[*].name` or `links[0].href`.
+//!
+//! Repeated fields select every element when the index is omitted, and their sub-field defaults
+//! to the most commonly used one (`authors` is `authors[*].name`).
+
+use std::{fmt, str::FromStr};
+
+use anyhow::anyhow;
+
+use super::{Category, Content, Entry, Link, Person};
+use crate::node::Field;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Index {
+	All,
+	At(usize),
+}
+
+macro_rules! sub_field {
+	($name:ident, $ty:ty, { $($variant:ident => $field:ident: $key:literal),+ $(,)? }) => {
+		#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+		pub enum $name {
+			$($variant),+
+		}
+
+		impl $name {
+			fn as_str(self) -> &'static str {
+				match self {
+					$(Self::$variant => $key),+
+				}
+			}
+
+			fn get(self, value: &$ty) -> &String {
+				match self {
+					$(Self::$variant => &value.$field),+
+				}
+			}
+
+			fn get_mut(self, value: &mut $ty) -> &mut String {
+				match self {
+					$(Self::$variant => &mut value.$field),+
+				}
+			}
+		}
+
+		impl FromStr for $name {
+			type Err = anyhow::Error;
+
+			fn from_str(s: &str) -> Result<Self, Self::Err> {
+				match s {
+					$($key => Ok(Self::$variant),)+
+					_ => Err(anyhow!("unknown {} field {s}", stringify!($ty).to_lowercase())),
+				}
+			}
+		}
+	};
+}
+
+sub_field!(ContentField, Content, {
+	Value => value: "value",
+	Type => content_type: "type",
+	Lang => lang: "lang",
+	Src => src: "src",
+});
+
+sub_field!(PersonField, Person, {
+	Name => name: "name",
+	Email => email: "email",
+	Uri => uri: "uri",
+});
+
+sub_field!(CategoryField, Category, {
+	Term => term: "term",
+	Scheme => scheme: "scheme",
+	Label => label: "label",
+});
+
+sub_field!(LinkField, Link, {
+	Href => href: "href",
+	Rel => rel: "rel",
+	MediaType => media_type: "media_type",
+	Title => title: "title",
+	Hreflang => hreflang: "hreflang",
+	Length => length: "length",
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldPath {
+	Title,
+	Id,
+	Summary,
+	Rights,
+	Content(ContentField),
+	Authors(Index, PersonField),
+	Contributors(Index, PersonField),
+	Categories(Index, CategoryField),
+	Links(Index, LinkField),
+}
+
+fn select<T>(items: &[T], index: Index) -> &[T] {
+	match index {
+		Index::All => items,
+		Index::At(i) => items.get(i..=i).unwrap_or_default(),
+	}
+}
+
+fn select_mut<T>(items: &mut [T], index: Index) -> &mut [T] {
+	match index {
+		Index::All => items,
+		Index::At(i) => items.get_mut(i..=i).unwrap_or_default(),
+	}
+}
+
+/// Splits `name[index]` into its parts.
+fn parse_segment(segment: &str) -> anyhow::Result<(&str, Option<Index>)> {
+	let Some((name, index)) = segment.split_once('[') else {
+		return Ok((segment, None));
+	};
+	let index = index
+		.strip_suffix(']')
+		.ok_or_else(|| anyhow!("unclosed index in {segment}"))?;
+
+	let index = match index {
+		"*" => Index::All,
+		i => Index::At(i.parse().map_err(|_| anyhow!("invalid index {i}"))?),
+	};
+	Ok((name, Some(index)))
+}
+
+fn sub_field<T: FromStr<Err = anyhow::Error>>(rest: Option<&str>, default: T) -> anyhow::Result<T> {
+	rest.map_or(Ok(default), str::parse)
+}
+
+impl FromStr for FieldPath {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (head, rest) = match s.split_once('.') {
+			Some((head, rest)) => (head, Some(rest)),
+			None => (s, None),
+		};
+		let (name, index) = parse_segment(head.trim())?;
+
+		let repeated = matches!(name, "authors" | "contributors" | "categories" | "links");
+		if index.is_some() && !repeated {
+			return Err(anyhow!("{name} is not a repeated field"));
+		}
+		let index = index.unwrap_or(Index::All);
+
+		Ok(match (name, rest) {
+			("title", None) => Self::Title,
+			("id", None) => Self::Id,
+			("summary", None | Some("value")) => Self::Summary,
+			("rights", None | Some("value")) => Self::Rights,
+			("content", rest) => Self::Content(sub_field(rest, ContentField::Value)?),
+			("authors", rest) => Self::Authors(index, sub_field(rest, PersonField::Name)?),
+			("contributors", rest) => {
+				Self::Contributors(index, sub_field(rest, PersonField::Name)?)
+			}
+			("categories", rest) => Self::Categories(index, sub_field(rest, CategoryField::Term)?),
+			("links", rest) => Self::Links(index, sub_field(rest, LinkField::Href)?),
+			_ => return Err(anyhow!("unknown field {s}")),
+		})
+	}
+}
+
+impl fmt::Display for Index {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Index::All => write!(f, "[*]"),
+			Index::At(i) => write!(f, "[{i}]"),
+		}
+	}
+}
+
+impl fmt::Display for FieldPath {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Title => write!(f, "title"),
+			Self::Id => write!(f, "id"),
+			Self::Summary => write!(f, "summary"),
+			Self::Rights => write!(f, "rights"),
+			Self::Content(field) => write!(f, "content.{}", field.as_str()),
+			Self::Authors(index, field) => write!(f, "authors{index}.{}", field.as_str()),
+			Self::Contributors(index, field) => {
+				write!(f, "contributors{index}.{}", field.as_str())
+			}
+			Self::Categories(index, field) => write!(f, "categories{index}.{}", field.as_str()),
+			Self::Links(index, field) => write!(f, "links{index}.{}", field.as_str()),
+		}
+	}
+}
+
+/// The fields addressable before path expressions existed.
+impl From<Field> for FieldPath {
+	fn from(field: Field) -> Self {
+		match field {
+			Field::Author => Self::Authors(Index::All, PersonField::Name),
+			Field::Summary => Self::Summary,
+			Field::Content => Self::Content(ContentField::Value),
+			Field::Title => Self::Title,
+		}
+	}
+}
+
+impl Entry {
+	/// Every value addressed by `path`, empty if the entry doesn't have the field.
+	#[must_use]
+	pub fn values(&self, path: FieldPath) -> Vec<&String> {
+		match path {
+			FieldPath::Title => vec![&self.title],
+			FieldPath::Id => vec![&self.id],
+			FieldPath::Summary => self.summary.iter().map(|t| &t.value).collect(),
+			FieldPath::Rights => self.rights.iter().map(|t| &t.value).collect(),
+			FieldPath::Content(field) => self.content.iter().map(|c| field.get(c)).collect(),
+			FieldPath::Authors(index, field) => select(&self.authors, index)
+				.iter()
+				.map(|p| field.get(p))
+				.collect(),
+			FieldPath::Contributors(index, field) => select(&self.contributors, index)
+				.iter()
+				.map(|p| field.get(p))
+				.collect(),
+			FieldPath::Categories(index, field) => select(&self.categories, index)
+				.iter()
+				.map(|c| field.get(c))
+				.collect(),
+			FieldPath::Links(index, field) => select(&self.links, index)
+				.iter()
+				.map(|l| field.get(l))
+				.collect(),
+		}
+	}
+
+	pub fn values_mut(&mut self, path: FieldPath) -> Vec<&mut String> {
+		match path {
+			FieldPath::Title => vec![&mut self.title],
+			FieldPath::Id => vec![&mut self.id],
+			FieldPath::Summary => self.summary.iter_mut().map(|t| &mut t.value).collect(),
+			FieldPath::Rights => self.rights.iter_mut().map(|t| &mut t.value).collect(),
+			FieldPath::Content(field) => {
+				self.content.iter_mut().map(|c| field.get_mut(c)).collect()
+			}
+			FieldPath::Authors(index, field) => select_mut(&mut self.authors, index)
+				.iter_mut()
+				.map(|p| field.get_mut(p))
+				.collect(),
+			FieldPath::Contributors(index, field) => select_mut(&mut self.contributors, index)
+				.iter_mut()
+				.map(|p| field.get_mut(p))
+				.collect(),
+			FieldPath::Categories(index, field) => select_mut(&mut self.categories, index)
+				.iter_mut()
+				.map(|c| field.get_mut(c))
+				.collect(),
+			FieldPath::Links(index, field) => select_mut(&mut self.links, index)
+				.iter_mut()
+				.map(|l| field.get_mut(l))
+				.collect(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{CategoryField, ContentField, FieldPath, Index, LinkField, PersonField};
+	use crate::feed::{Content, Entry, Link, Person};
+
+	fn path(s: &str) -> FieldPath {
+		s.parse().unwrap()
+	}
+
+	fn entry() -> Entry {
+		Entry {
+			title: "Title".to_string(),
+			authors: vec![
+				Person {
+					name: "Jane".to_string(),
+					email: "jane@example.org".to_string(),
+					..Person::default()
+				},
+				Person {
+					name: "John".to_string(),
+					..Person::default()
+				},
+			],
+			links: vec![
+				Link {
+					href: "http://example.org/1".to_string(),
+					rel: "alternate".to_string(),
+					..Link::default()
+				},
+				Link {
+					href: "http://example.org/1.mp3".to_string(),
+					rel: "enclosure".to_string(),
+					length: "1234".to_string(),
+					..Link::default()
+				},
+			],
+			content: Some(Content {
+				value: "<p>Hi</p>".to_string(),
+				content_type: "html".to_string(),
+				..Content::default()
+			}),
+			..Entry::default()
+		}
+	}
+
+	#[test]
+	fn parse() {
+		assert_eq!(path("title"), FieldPath::Title);
+		assert_eq!(path("summary.value"), FieldPath::Summary);
+		assert_eq!(
+			path("authors[*].name"),
+			FieldPath::Authors(Index::All, PersonField::Name)
+		);
+		assert_eq!(
+			path("links[0].href"),
+			FieldPath::Links(Index::At(0), LinkField::Href)
+		);
+		assert_eq!(
+			path("links[1].length"),
+			FieldPath::Links(Index::At(1), LinkField::Length)
+		);
+		assert_eq!(path("content.type"), FieldPath::Content(ContentField::Type));
+	}
+
+	#[test]
+	fn default_sub_fields() {
+		assert_eq!(path("content"), FieldPath::Content(ContentField::Value));
+		assert_eq!(
+			path("authors"),
+			FieldPath::Authors(Index::All, PersonField::Name)
+		);
+		assert_eq!(
+			path("categories[2]"),
+			FieldPath::Categories(Index::At(2), CategoryField::Term)
+		);
+		assert_eq!(path("links"), FieldPath::Links(Index::All, LinkField::Href));
+	}
+
+	#[test]
+	fn display_round_trips() {
+		for s in [
+			"title",
+			"content.type",
+			"authors[*].name",
+			"links[0].href",
+			"links[*].length",
+		] {
+			assert_eq!(path(s).to_string(), s);
+		}
+		assert_eq!(path("authors").to_string(), "authors[*].name");
+	}
+
+	#[test]
+	fn errors() {
+		for (s, message) in [
+			("title[0]", "title is not a repeated field"),
+			("content[*].type", "content is not a repeated field"),
+			("subtitle", "unknown field subtitle"),
+			("authors[0", "unclosed index in authors[0"),
+			("authors[x].name", "invalid index x"),
+			("authors.nickname", "unknown person field nickname"),
+			("title.value", "unknown field title.value"),
+		] {
+			let err = s.parse::<FieldPath>().unwrap_err();
+			assert_eq!(err.to_string(), message, "{s}");
+		}
+	}
+
+	#[test]
+	fn values() {
+		let entry = entry();
+		assert_eq!(entry.values(path("title")), ["Title"]);
+		assert_eq!(entry.values(path("authors[*].name")), ["Jane", "John"]);
+		assert_eq!(entry.values(path("authors[1].email")), [""]);
+		assert_eq!(
+			entry.values(path("links[0].href")),
+			["http://example.org/1"]
+		);
+		assert_eq!(entry.values(path("links[1].length")), ["1234"]);
+		assert_eq!(entry.values(path("content.type")), ["html"]);
+		// Missing fields and out of range indices address nothing.
+		assert!(entry.values(path("summary")).is_empty());
+		assert!(entry.values(path("links[5].href")).is_empty());
+	}
+
+	#[test]
+	fn values_mut() {
+		let mut entry = entry();
+		for name in entry.values_mut(path("authors")) {
+			*name = name.to_uppercase();
+		}
+		*entry.values_mut(path("links[0].href"))[0] = "http://example.org/one".to_string();
+
+		assert_eq!(entry.authors[0].name, "JANE");
+		assert_eq!(entry.authors[1].name, "JOHN");
+		assert_eq!(entry.links[0].href, "http://example.org/one");
+		assert_eq!(entry.links[1].href, "http://example.org/1.mp3");
+	}
+}
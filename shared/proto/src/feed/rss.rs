@@ -0,0 +1,313 @@
+use ::rss::{
+	Category as RssCategory, Channel, Enclosure, Guid, Image, Item,
+	extension::{atom::AtomExtension, dublincore::DublinCoreExtension},
+};
+use chrono::DateTime;
+use prost_types::Timestamp;
+
+use super::{
+	Category, Content, Entry, Feed, Generator, Link, Person, Text, TextType, from_timestamp,
+	non_empty, to_timestamp,
+};
+
+/// RSS only allows an email address in `author`, in the `email (name)` form.
+fn author(person: &Person) -> Option<String> {
+	if person.email.is_empty() {
+		None
+	} else if person.name.is_empty() {
+		Some(person.email.clone())
+	} else {
+		Some(format!("{} ({})", person.email, person.name))
+	}
+}
+
+fn parse_author(author: &str) -> Person {
+	let author = author.trim();
+	if let Some((email, name)) = author.strip_suffix(')').and_then(|a| a.split_once(" (")) {
+		Person {
+			name: name.to_string(),
+			email: email.to_string(),
+			..Person::default()
+		}
+	} else if author.contains('@') {
+		Person {
+			email: author.to_string(),
+			..Person::default()
+		}
+	} else {
+		Person {
+			name: author.to_string(),
+			..Person::default()
+		}
+	}
+}
+
+/// Names of all authors as `dc:creator`s, as they can't all be represented in `author`.
+fn creators(authors: &[Person]) -> Option<DublinCoreExtension> {
+	if authors.is_empty() {
+		None
+	} else {
+		Some(DublinCoreExtension {
+			creators: authors.iter().map(|p| p.name.clone()).collect(),
+			..DublinCoreExtension::default()
+		})
+	}
+}
+
+fn people(author: Option<&str>, dc: Option<&DublinCoreExtension>) -> Vec<Person> {
+	let mut people: Vec<Person> = author.map(parse_author).into_iter().collect();
+	for creator in dc.iter().flat_map(|dc| &dc.creators) {
+		if !people.iter().any(|p| &p.name == creator) {
+			people.push(Person {
+				name: creator.clone(),
+				..Person::default()
+			});
+		}
+	}
+	people
+}
+
+/// RSS 2.0 dates are RFC 2822, RSS 1.0 `dc:date`s are W3C-DTF (RFC 3339).
+fn parse_date(date: &str) -> Option<Timestamp> {
+	let date = date.trim();
+	DateTime::parse_from_rfc2822(date)
+		.or_else(|_| DateTime::parse_from_rfc3339(date))
+		.ok()
+		.map(|d| to_timestamp(&d))
+}
+
+fn dc_date(dc: Option<&DublinCoreExtension>) -> Option<Timestamp> {
+	dc.and_then(|dc| dc.dates.first())
+		.and_then(|d| parse_date(d))
+}
+
+fn link(href: &str, rel: &str) -> Link {
+	Link {
+		href: href.to_string(),
+		rel: rel.to_string(),
+		..Link::default()
+	}
+}
+
+/// Adds the `atom:link`s of an item or channel that aren't already represented.
+fn extend_links(links: &mut Vec<Link>, atom: Option<&AtomExtension>) {
+	for l in atom.iter().flat_map(|a| &a.links) {
+		if !links.iter().any(|e| e.href == l.href && e.rel == l.rel) {
+			links.push(l.into());
+		}
+	}
+}
+
+fn is_alternate(link: &Link) -> bool {
+	link.rel.is_empty() || link.rel == "alternate"
+}
+
+impl From<&RssCategory> for Category {
+	fn from(category: &RssCategory) -> Self {
+		Category {
+			term: category.name.clone(),
+			scheme: category.domain.clone().unwrap_or_default(),
+			..Category::default()
+		}
+	}
+}
+
+impl From<Category> for RssCategory {
+	fn from(category: Category) -> Self {
+		RssCategory {
+			name: category.term,
+			domain: non_empty(category.scheme),
+		}
+	}
+}
+
+impl From<&Item> for Entry {
+	fn from(item: &Item) -> Self {
+		let dc = item.dublin_core_ext.as_ref();
+		let date = item
+			.pub_date
+			.as_deref()
+			.and_then(parse_date)
+			.or_else(|| dc_date(dc));
+
+		let mut links = Vec::new();
+		if let Some(href) = &item.link {
+			links.push(link(href, "alternate"));
+		}
+		if let Some(enclosure) = &item.enclosure {
+			links.push(Link {
+				href: enclosure.url.clone(),
+				rel: "enclosure".to_string(),
+				media_type: enclosure.mime_type.clone(),
+				length: enclosure.length.clone(),
+				..Link::default()
+			});
+		}
+		if let Some(href) = &item.comments {
+			links.push(link(href, "replies"));
+		}
+		extend_links(&mut links, item.atom_ext.as_ref());
+
+		Entry {
+			title: item.title.clone().unwrap_or_default(),
+			id: item
+				.guid
+				.as_ref()
+				.map(|g| g.value.clone())
+				.or_else(|| item.link.clone())
+				.or_else(|| item.title.clone())
+				.unwrap_or_default(),
+			updated: date,
+			published: date,
+			authors: people(item.author.as_deref(), dc),
+			links,
+			summary: item.description.as_ref().map(|d| Text {
+				value: d.clone(),
+				r#type: TextType::Html as i32,
+			}),
+			content: item.content.as_ref().map(|c| Content {
+				value: c.clone(),
+				content_type: "html".to_string(),
+				..Content::default()
+			}),
+			categories: item.categories.iter().map(Into::into).collect(),
+			..Entry::default()
+		}
+	}
+}
+
+impl From<&Channel> for Feed {
+	fn from(channel: &Channel) -> Self {
+		let entries: Vec<Entry> = channel.items.iter().map(Into::into).collect();
+		let dc = channel.dublin_core_ext.as_ref();
+
+		let updated = channel
+			.last_build_date
+			.as_deref()
+			.and_then(parse_date)
+			.or_else(|| channel.pub_date.as_deref().and_then(parse_date))
+			.or_else(|| dc_date(dc))
+			.or_else(|| {
+				entries
+					.iter()
+					.filter_map(|e| e.updated)
+					.max_by_key(|t| (t.seconds, t.nanos))
+			});
+
+		let mut links = Vec::new();
+		if !channel.link.is_empty() {
+			links.push(link(&channel.link, "alternate"));
+		}
+		extend_links(&mut links, channel.atom_ext.as_ref());
+
+		Feed {
+			title: channel.title.clone(),
+			id: channel.link.clone(),
+			updated,
+			authors: people(channel.managing_editor.as_deref(), dc),
+			entries,
+			links,
+			subtitle: non_empty(channel.description.clone()).map(|value| Text {
+				value,
+				r#type: TextType::Html as i32,
+			}),
+			logo: channel
+				.image
+				.as_ref()
+				.map(|i| i.url.clone())
+				.unwrap_or_default(),
+			generator: channel.generator.as_ref().map(|value| Generator {
+				value: value.clone(),
+				..Generator::default()
+			}),
+			rights: channel.copyright.as_ref().map(|value| Text {
+				value: value.clone(),
+				r#type: TextType::Text as i32,
+			}),
+			categories: channel.categories.iter().map(Into::into).collect(),
+			lang: channel.language.clone().unwrap_or_default(),
+			..Feed::default()
+		}
+	}
+}
+
+impl From<Entry> for Item {
+	fn from(entry: Entry) -> Self {
+		let (mut alternate, mut enclosure) = (None, None);
+		let mut links = Vec::new();
+		for l in entry.links {
+			if alternate.is_none() && is_alternate(&l) {
+				alternate = Some(l.href);
+			} else if enclosure.is_none() && l.rel == "enclosure" {
+				enclosure = Some(Enclosure {
+					url: l.href,
+					length: l.length,
+					mime_type: l.media_type,
+				});
+			} else {
+				links.push(l.into());
+			}
+		}
+
+		Item {
+			title: Some(entry.title),
+			guid: Some(Guid {
+				permalink: alternate.as_ref() == Some(&entry.id),
+				value: entry.id,
+			}),
+			link: alternate,
+			enclosure,
+			pub_date: entry
+				.published
+				.or(entry.updated)
+				.and_then(from_timestamp)
+				.map(|d| d.to_rfc2822()),
+			author: entry.authors.iter().find_map(author),
+			dublin_core_ext: creators(&entry.authors),
+			categories: entry.categories.into_iter().map(Into::into).collect(),
+			description: entry.summary.map(|t| t.value),
+			content: entry.content.map(|c| c.value),
+			atom_ext: (!links.is_empty()).then_some(AtomExtension { links }),
+			..Item::default()
+		}
+	}
+}
+
+impl From<Feed> for Channel {
+	fn from(feed: Feed) -> Self {
+		let alternate = feed.links.iter().position(is_alternate);
+		let link = alternate.map_or(feed.id, |i| feed.links[i].href.clone());
+		let links: Vec<_> = feed
+			.links
+			.into_iter()
+			.enumerate()
+			.filter(|(i, _)| Some(*i) != alternate)
+			.map(|(_, l)| l.into())
+			.collect();
+
+		Channel {
+			image: non_empty(feed.logo).map(|url| Image {
+				url,
+				title: feed.title.clone(),
+				link: link.clone(),
+				..Image::default()
+			}),
+			title: feed.title,
+			link,
+			description: feed.subtitle.map(|t| t.value).unwrap_or_default(),
+			generator: feed.generator.map(|g| g.value),
+			copyright: feed.rights.map(|t| t.value),
+			categories: feed.categories.into_iter().map(Into::into).collect(),
+			language: non_empty(feed.lang),
+			last_build_date: feed
+				.updated
+				.and_then(from_timestamp)
+				.map(|d| d.to_rfc2822()),
+			managing_editor: feed.authors.iter().find_map(author),
+			dublin_core_ext: creators(&feed.authors),
+			items: feed.entries.into_iter().map(Into::into).collect(),
+			atom_ext: (!links.is_empty()).then_some(AtomExtension { links }),
+			..Channel::default()
+		}
+	}
+}
@@ -2,9 +2,12 @@
 
 pub mod feed;
 pub mod node {
+	use std::str::FromStr;
+
+	use prost_types::value::Kind;
 	use tonic::Status;
 
-	use crate::node::tfv::TryFromValue;
+	use crate::{feed::path::FieldPath, node::tfv::TryFromValue};
 
 	tonic::include_proto!("rssflow.node");
 
@@ -31,6 +34,75 @@ pub mod node {
 				None => Err(Status::invalid_argument(format!("{key} option is missing")))?,
 			}
 		}
+
+		/// Reads a required field option, either a path expression or a legacy `Field` value.
+		pub fn get_field(&self, key: &str) -> Result<FieldPath, Status> {
+			let value = self.options.as_ref().and_then(|o| o.fields.get(key));
+			match value.and_then(|v| v.kind.as_ref()) {
+				Some(Kind::StringValue(path)) => FieldPath::from_str(path)
+					.map_err(|e| Status::invalid_argument(format!("invalid {key} option: {e}"))),
+				Some(Kind::NumberValue(n)) => Field::try_from(*n as i32)
+					.map(Into::into)
+					.map_err(|_| Status::invalid_argument("not a valid field enum value")),
+				Some(_) => Err(Status::invalid_argument(format!(
+					"wrong type for {key} option"
+				))),
+				None => Err(Status::invalid_argument(format!("{key} option is missing"))),
+			}
+		}
+	}
+
+	impl OptionSchema {
+		#[must_use]
+		pub fn required(name: &str, r#type: OptionType) -> Self {
+			let values = match r#type {
+				OptionType::Field => Field::names(),
+				_ => Vec::new(),
+			};
+
+			Self {
+				name: name.to_string(),
+				r#type: r#type as i32,
+				required: true,
+				values,
+				..Self::default()
+			}
+		}
+
+		#[must_use]
+		pub fn optional(name: &str, r#type: OptionType) -> Self {
+			Self {
+				required: false,
+				..Self::required(name, r#type)
+			}
+		}
+
+		#[must_use]
+		pub fn with_description(mut self, description: &str) -> Self {
+			self.description = description.to_string();
+			self
+		}
+
+		#[must_use]
+		pub fn with_default(mut self, default: impl Into<prost_types::Value>) -> Self {
+			self.default = Some(default.into());
+			self
+		}
+
+		#[must_use]
+		pub fn with_values(mut self, values: &[&str]) -> Self {
+			self.values = values.iter().map(ToString::to_string).collect();
+			self
+		}
+	}
+
+	impl Field {
+		fn names() -> Vec<String> {
+			[Field::Author, Field::Summary, Field::Content, Field::Title]
+				.iter()
+				.map(|f| f.as_str_name().to_string())
+				.collect()
+		}
 	}
 
 	pub(crate) mod tfv {
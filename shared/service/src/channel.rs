@@ -0,0 +1,99 @@
+use std::{
+	collections::HashMap,
+	str::FromStr,
+	sync::{Mutex, OnceLock},
+	time::Duration,
+};
+
+use anyhow::Context;
+use figment::{Figment, providers::Serialized};
+use serde::{Deserialize, Serialize};
+use tonic::transport::{Channel, Endpoint};
+
+/// Timeouts and keepalive of pooled channels, in seconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChannelConfig {
+	pub connect_timeout: u64,
+	/// Time allowed for a request to be answered, `0` to wait forever.
+	pub request_timeout: u64,
+	pub keepalive_interval: u64,
+	pub keepalive_timeout: u64,
+}
+
+impl Default for ChannelConfig {
+	fn default() -> Self {
+		ChannelConfig {
+			connect_timeout: 5,
+			request_timeout: 60,
+			keepalive_interval: 30,
+			keepalive_timeout: 10,
+		}
+	}
+}
+
+impl ChannelConfig {
+	fn endpoint(&self, address: &str) -> anyhow::Result<Endpoint> {
+		let endpoint = Endpoint::from_str(address)
+			.with_context(|| format!("create endpoint {address}"))?
+			.connect_timeout(Duration::from_secs(self.connect_timeout))
+			.http2_keep_alive_interval(Duration::from_secs(self.keepalive_interval))
+			.keep_alive_timeout(Duration::from_secs(self.keepalive_timeout))
+			.keep_alive_while_idle(true);
+
+		Ok(match self.request_timeout {
+			0 => endpoint,
+			secs => endpoint.timeout(Duration::from_secs(secs)),
+		})
+	}
+}
+
+/// gRPC channels by address, shared by every client of the process.
+///
+/// Channels connect on first use and reconnect by themselves when the connection drops.
+#[derive(Debug, Default)]
+pub struct ChannelPool {
+	config: ChannelConfig,
+	channels: Mutex<HashMap<String, Channel>>,
+}
+
+impl ChannelPool {
+	#[must_use]
+	pub fn new(config: ChannelConfig) -> Self {
+		ChannelPool {
+			config,
+			channels: Mutex::default(),
+		}
+	}
+
+	/// The channel to `address`, created if there's none yet.
+	pub fn get(&self, address: &str) -> anyhow::Result<Channel> {
+		let mut channels = self.channels.lock().unwrap();
+		if let Some(channel) = channels.get(address) {
+			return Ok(channel.clone());
+		}
+
+		let channel = self.config.endpoint(address)?.connect_lazy();
+		channels.insert(address.to_string(), channel.clone());
+		Ok(channel)
+	}
+
+	/// Drops the channel to `address`, so the next request starts over, resolving the address
+	/// again.
+	pub fn evict(&self, address: &str) {
+		self.channels.lock().unwrap().remove(address);
+	}
+}
+
+/// The process-wide channel pool, configured from the `channel` table.
+pub fn channels() -> &'static ChannelPool {
+	static POOL: OnceLock<ChannelPool> = OnceLock::new();
+
+	POOL.get_or_init(|| {
+		let config = Figment::from(Serialized::default("channel", ChannelConfig::default()))
+			.merge(runesys::config::FIGMENT.clone())
+			.extract_inner("channel")
+			.unwrap();
+		ChannelPool::new(config)
+	})
+}
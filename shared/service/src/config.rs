@@ -13,6 +13,8 @@ pub struct ServiceConfig {
 	pub registry_url: Url,
 	pub public_url: Option<Url>,
 	pub service_url: Option<Url>,
+	/// The WebSub service, which nodes subscribe to feeds through.
+	pub websub_url: Url,
 }
 
 impl Default for ServiceConfig {
@@ -21,6 +23,7 @@ impl Default for ServiceConfig {
 			registry_url: Url::parse("http://rssflow:50051").expect("Hardcoded URL"),
 			public_url: None,
 			service_url: None,
+			websub_url: Url::parse("http://websub:50051").expect("Hardcoded URL"),
 		}
 	}
 }
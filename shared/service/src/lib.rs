@@ -1,33 +1,38 @@
 #![warn(clippy::pedantic)]
 
-use std::{error::Error, str::FromStr, time::Duration};
+use std::time::Duration;
 
-use anyhow::Context;
 pub use rssflow_proto as proto;
-use rssflow_proto::node::{
-	NodeMeta, PingResponse, ProcessRequest, ProcessResponse, node_service_client::NodeServiceClient,
-};
-use runesys::{
-	telemetry,
-	util::{retry_async, try_from_any},
+use rssflow_proto::{
+	node::{
+		NodeDescription, NodeMeta, PingResponse, ProcessRequest, ProcessResponse,
+		node_service_client::NodeServiceClient,
+	},
+	registry::{
+		DeregisterRequest, HeartbeatRequest, RegisterRequest,
+		node_registry_client::NodeRegistryClient,
+	},
 };
+use runesys::{telemetry, util::try_from_any};
 use tonic::{
-	Request, Response, Status,
-	codegen::InterceptedService,
-	server::NamedService,
-	service::Interceptor,
-	transport::{Channel, Endpoint},
+	Code, Request, Response, Status, codegen::InterceptedService, server::NamedService,
+	service::Interceptor, transport::Channel,
 };
 use tonic_health::pb::health_client::HealthClient;
+use tracing::{error, info, warn};
 use url::Url;
 
-use crate::config::ServiceConfig;
+use crate::{channel::channels, config::ServiceConfig};
 
+pub mod channel;
 pub mod config;
 
+/// Response metadata in which a node reports whether its source is pushed to it over WebSub.
+pub const X_WEBSUB: &str = "x-websub";
+
 pub trait NodeExt {
-	fn endpoint(&self) -> anyhow::Result<Endpoint>;
-	async fn channel(&self) -> anyhow::Result<Channel>;
+	/// The pooled channel to the node.
+	fn channel(&self) -> anyhow::Result<Channel>;
 
 	#[cfg(not(feature = "telemetry"))]
 	async fn client(&self) -> anyhow::Result<NodeServiceClient<Channel>>;
@@ -43,21 +48,13 @@ pub trait NodeExt {
 }
 
 impl NodeExt for NodeMeta {
-	fn endpoint(&self) -> anyhow::Result<Endpoint> {
-		Endpoint::from_str(&self.address)
-			.with_context(|| format!("create endpoint {}", self.address))
-	}
-
-	async fn channel(&self) -> anyhow::Result<Channel> {
-		self.endpoint()?
-			.connect()
-			.await
-			.with_context(|| format!("connect to endpoint {}", self.address))
+	fn channel(&self) -> anyhow::Result<Channel> {
+		channels().get(&self.address)
 	}
 
 	#[cfg(not(feature = "telemetry"))]
 	async fn client(&self) -> anyhow::Result<NodeServiceClient<Channel>> {
-		Ok(NodeServiceClient::new(self.channel().await?))
+		Ok(NodeServiceClient::new(self.channel()?))
 	}
 
 	#[cfg(feature = "telemetry")]
@@ -65,19 +62,27 @@ impl NodeExt for NodeMeta {
 		&self,
 	) -> anyhow::Result<NodeServiceClient<InterceptedService<Channel, impl Interceptor>>> {
 		Ok(NodeServiceClient::with_interceptor(
-			self.channel().await?,
+			self.channel()?,
 			interceptor(telemetry::propagation::send_trace),
 		))
 	}
 
 	async fn health(&self) -> anyhow::Result<HealthClient<Channel>> {
-		Ok(HealthClient::new(self.channel().await?))
+		Ok(HealthClient::new(self.channel()?))
 	}
 
 	async fn process(&self, req: ProcessRequest) -> anyhow::Result<Response<ProcessResponse>> {
 		let mut req = Request::new(req);
 		req.metadata_mut().insert("x-node", self.node_name.parse()?);
-		Ok(self.client().await?.process(req).await?)
+
+		let response = self.client().await?.process(req).await;
+		if response
+			.as_ref()
+			.is_err_and(|status| status.code() == Code::Unavailable)
+		{
+			channels().evict(&self.address);
+		}
+		Ok(response?)
 	}
 }
 
@@ -88,6 +93,10 @@ pub trait ServiceExt2 {
 	fn node_meta() -> NodeMeta;
 
 	fn respond_to_ping() -> Result<Response<PingResponse>, Status>;
+
+	fn respond_to_describe(
+		description: NodeDescription,
+	) -> Result<Response<NodeDescription>, Status>;
 }
 
 impl<T> ServiceExt2 for T
@@ -112,69 +121,147 @@ where
 			node: Some(Self::node_meta()),
 		}))
 	}
+
+	fn respond_to_describe(
+		description: NodeDescription,
+	) -> Result<Response<NodeDescription>, Status> {
+		Ok(Response::new(NodeDescription {
+			node_name: Self::INFO.name.to_string(),
+			..description
+		}))
+	}
+}
+
+/// Heartbeat interval used until the registry tells otherwise.
+const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+impl<S> ServiceExt for runesys::service::ServiceBuilder<S>
+where
+	S: runesys::Service + 'static,
+	S::Server: NamedService,
+{
+	/// Registers the node with the registry at `registry_url` and keeps it registered until
+	/// shutdown, when it deregisters.
+	fn with_reporter(self) -> Self {
+		let config = config::config::<S>();
+
+		self.with_task(async move {
+			let node = S::node_meta();
+
+			tokio::select! {
+				() = keep_registered(config, &node) => {}
+				() = shutdown_signal() => {}
+			}
+
+			if let Err(err) = deregister(config, &node).await {
+				warn!("Deregistering from {} failed: {err:#}", config.registry_url);
+			}
+
+			futures::future::pending::<()>().await;
+			unreachable!()
+		})
+	}
+}
+
+/// Resolves on Ctrl-C or, on Unix, SIGTERM, which Docker and Kubernetes stop containers with.
+async fn shutdown_signal() {
+	#[cfg(unix)]
+	let terminate = async {
+		match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+			Ok(mut signal) => {
+				signal.recv().await;
+			}
+			Err(err) => {
+				error!("Listening for SIGTERM failed: {err}");
+				futures::future::pending::<()>().await;
+			}
+		}
+	};
+	#[cfg(not(unix))]
+	let terminate = futures::future::pending::<()>();
+
+	tokio::select! {
+		_ = tokio::signal::ctrl_c() => {}
+		() = terminate => {}
+	}
+}
+
+/// Client of the registry at `registry_url`.
+#[cfg(not(feature = "telemetry"))]
+pub fn registry_client(config: &ServiceConfig) -> anyhow::Result<NodeRegistryClient<Channel>> {
+	Ok(NodeRegistryClient::new(
+		channels().get(config.registry_url.as_str())?,
+	))
+}
+
+/// Client of the registry at `registry_url`.
+#[cfg(feature = "telemetry")]
+pub fn registry_client(
+	config: &ServiceConfig,
+) -> anyhow::Result<NodeRegistryClient<InterceptedService<Channel, impl Interceptor>>> {
+	Ok(NodeRegistryClient::with_interceptor(
+		channels().get(config.registry_url.as_str())?,
+		interceptor(telemetry::propagation::send_trace),
+	))
+}
+
+/// Registers `node` and sends heartbeats forever, registering again whenever the registry
+/// doesn't know the node, e.g. after it restarted.
+async fn keep_registered(config: &ServiceConfig, node: &NodeMeta) {
+	let mut client = match registry_client(config) {
+		Ok(client) => client,
+		Err(err) => {
+			error!("{err:#}");
+			return futures::future::pending().await;
+		}
+	};
+
+	let mut registered = false;
+	let mut interval = DEFAULT_HEARTBEAT_INTERVAL;
+	loop {
+		if registered {
+			let request = HeartbeatRequest {
+				node: Some(node.clone()),
+			};
+			match client.heartbeat(request).await {
+				Ok(response) => registered = response.into_inner().registered,
+				Err(status) => warn!("Heartbeat to {} failed: {status}", config.registry_url),
+			}
+		}
+
+		if !registered {
+			let request = RegisterRequest {
+				node: Some(node.clone()),
+			};
+			match client.register(request).await {
+				Ok(response) => {
+					info!("Registered with {}", config.registry_url);
+					registered = true;
+					if let Some(heartbeat_interval) = response
+						.into_inner()
+						.heartbeat_interval
+						.and_then(|d| Duration::try_from(d).ok())
+					{
+						interval = heartbeat_interval;
+					}
+				}
+				Err(status) => warn!("Registering with {} failed: {status}", config.registry_url),
+			}
+		}
+
+		tokio::time::sleep(interval).await;
+	}
 }
 
-// impl<S> ServiceExt for runesys::service::ServiceBuilder<S>
-// where
-// 	S: runesys::Service + 'static,
-// 	S::Server: NamedService,
-// {
-// 	fn with_reporter(self) -> Self {
-// 		let config = config::config::<S>();
-//
-// 		self.with_task(async {
-// 			tokio::time::sleep(Duration::from_secs(5)).await;
-// 			// report(S::INFO.name, config).await?;
-//
-// 			S::INFO
-//
-// 			futures::future::pending::<()>().await;
-// 			unreachable!()
-// 		})
-// 	}
-// }
-
-// pub async fn report(
-// 	name: &str,
-// 	config: &ServiceConfig,
-// ) -> Result<(), Box<dyn Error + Send + Sync>> {
-// 	retry_async(
-// 		|| async {
-// 			let endpoint = Endpoint::from_str(config.registry_url.as_str())
-// 				.with_context(|| format!("create endpoint {}", config.registry_url))?;
-// 			let channel = endpoint
-// 				.connect()
-// 				.await
-// 				.with_context(|| format!("connect to endpoint {}", config.registry_url))?;
-//
-// 			#[cfg(not(feature = "telemetry"))]
-// 			let mut client = NodeRegistryClient::new(channel);
-// 			#[cfg(feature = "telemetry")]
-// 			let mut client = NodeRegistryClient::with_interceptor(
-// 				channel,
-// 				interceptor(telemetry::propagation::send_trace),
-// 			);
-//
-// 			client
-// 				.register(RegisterRequest {
-// 					node: Some(Node {
-// 						address: config
-// 							.service_url
-// 							.as_ref()
-// 							.map(Url::to_string)
-// 							.unwrap_or_default(),
-// 						node_name: name.to_string(),
-// 					}),
-// 				})
-// 				.await?;
-//
-// 			Ok(())
-// 		},
-// 		3,
-// 		Duration::from_secs(2),
-// 	)
-// 	.await
-// }
+async fn deregister(config: &ServiceConfig, node: &NodeMeta) -> anyhow::Result<()> {
+	registry_client(config)?
+		.deregister(DeregisterRequest {
+			node: Some(node.clone()),
+		})
+		.await?;
+
+	Ok(())
+}
 
 pub fn check_node<S: runesys::Service>(request: &Request<ProcessRequest>) -> Result<(), Status> {
 	if let Some(node) = request.metadata().get("x-node") {
@@ -197,6 +284,28 @@ pub fn try_from_request<'a, T: TryFrom<&'a prost_types::Any> + prost::Name>(
 	try_from_any(payload)
 }
 
+/// Full protobuf name of a payload type, as listed in node descriptions.
+#[must_use]
+pub fn payload_type<T: prost::Name>() -> String {
+	T::full_name()
+}
+
+/// Decodes every payload of the request, for nodes that accept multiple inputs.
+pub fn try_all_from_request<'a, T: TryFrom<&'a prost_types::Any> + prost::Name>(
+	request: &'a ProcessRequest,
+) -> Result<Vec<T>, tonic::Status> {
+	if request.payload.is_none() && request.payloads.is_empty() {
+		return Err(Status::invalid_argument("payload missing"));
+	}
+
+	request
+		.payload
+		.iter()
+		.chain(&request.payloads)
+		.map(try_from_any)
+		.collect()
+}
+
 pub fn interceptor<T>(mutator: impl Fn(&mut T)) -> impl FnMut(T) -> Result<T, Status> {
 	move |mut value: T| {
 		mutator(&mut value);
@@ -2,30 +2,6 @@ use axum::{Router, http::StatusCode, routing::get};
 
 use crate::{RSSFlow, route};
 
-// #[derive(Clone)]
-// pub struct FlowHandle(Arc<Flow>, broadcast::Sender<Data>);
-// impl FlowHandle {
-// 	pub fn new(arc: Arc<Flow>) -> Self {
-// 		FlowHandle(arc, broadcast::channel(100).0)
-// 	}
-//
-// 	pub fn tx(&self) -> &broadcast::Sender<Data> {
-// 		&self.1
-// 	}
-//
-// 	pub fn subscribe(&self) -> broadcast::Receiver<Data> {
-// 		self.1.subscribe()
-// 	}
-// }
-//
-// impl Deref for FlowHandle {
-// 	type Target = Arc<Flow>;
-//
-// 	fn deref(&self) -> &Self::Target {
-// 		&self.0
-// 	}
-// }
-
 // fn load_flow(content: &str) -> anyhow::Result<Flow> {
 // 	let flow: FlowBuilder = serde_json::de::from_str(content)?;
 //
@@ -0,0 +1,49 @@
+use std::sync::OnceLock;
+
+use figment::{
+	Figment,
+	providers::{Env, Serialized},
+};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Where the registry looks for nodes, besides the ones registering themselves, and how
+/// flow feeds are published.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	/// Node endpoints, e.g. `http://fetch:50051`.
+	pub nodes: Vec<String>,
+	/// `host:port` names that resolve to any number of node instances.
+	pub hosts: Vec<String>,
+	/// DNS SRV records naming node instances, e.g. `_grpc._tcp.rssflow.example.com`.
+	pub srv: Vec<String>,
+	/// Where rssflow is reachable, e.g. `https://rssflow.example.com/`.
+	pub public_url: Option<Url>,
+	/// WebSub hub advertised in, and notified about, flow feeds. Requires `public_url`.
+	pub hub_url: Option<Url>,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			nodes: Vec::new(),
+			hosts: vec!["rssflow-headless:50051".into()],
+			srv: Vec::new(),
+			public_url: None,
+			hub_url: None,
+		}
+	}
+}
+
+pub fn config() -> &'static Config {
+	static CONFIG: OnceLock<Config> = OnceLock::new();
+
+	CONFIG.get_or_init(|| {
+		Figment::from(Serialized::defaults(Config::default()))
+			.merge(runesys::config::FIGMENT.clone())
+			.merge(Env::prefixed("RSSFLOW_"))
+			.extract()
+			.unwrap()
+	})
+}
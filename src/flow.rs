@@ -1,8 +1,27 @@
-use std::collections::BTreeMap;
+use std::{
+	collections::{BTreeMap, HashMap, HashSet},
+	time::Instant,
+};
 
-use prost_types::Struct;
-use rssflow_service::proto::node::Field;
+use axum::http::StatusCode;
+use chrono::Utc;
+use futures::{StreamExt, stream::FuturesUnordered};
+use prost_types::{Any, Struct};
+use rssflow_service::{
+	NodeExt, X_WEBSUB,
+	proto::node::{Field, FlowContext, ProcessRequest},
+};
 use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use self::run::NodeTrace;
+pub use self::{snapshot::Outputs, validate::ValidationError};
+use crate::registry::Nodes;
+
+pub mod revision;
+pub mod run;
+pub mod snapshot;
+mod validate;
 
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(untagged)]
@@ -30,8 +49,11 @@ pub fn to_struct(map: BTreeMap<String, Value>) -> Struct {
 	}
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct NodeOptions {
+	/// Name used to reference this node in connections, defaults to its index in the node list.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub id: Option<String>,
 	#[serde(rename = "type")]
 	pub r#type: String,
 	#[serde(flatten)]
@@ -46,95 +68,448 @@ impl NodeOptions {
 			Some(to_struct(self.options.clone()))
 		}
 	}
+
+	fn id(&self, index: usize) -> String {
+		self.id.clone().unwrap_or_else(|| index.to_string())
+	}
 }
 
-#[derive(Serialize, Deserialize)]
+/// Feeds the output of the `from` node into the inputs of the `to` node.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Connection {
+	pub from: String,
+	pub to: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Flow {
 	pub nodes: Vec<NodeOptions>,
+	/// Edges between nodes, by id. If empty, nodes are chained in list order.
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub connections: Vec<Connection>,
+	/// Seconds between scheduled runs. Flows without one only run when requested.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub interval: Option<u64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("Duplicate node id: {0}")]
+	DuplicateId(String),
+	#[error("Connection references unknown node id: {0}")]
+	UnknownId(String),
+	#[error("Flow contains a cycle through node {0}")]
+	Cycle(String),
+	#[error("No such node: {0}")]
+	UnknownNode(String),
+	#[error("Node {id} failed: {source}")]
+	Process { id: String, source: anyhow::Error },
+}
+
+impl Error {
+	pub fn status_code(&self) -> StatusCode {
+		match self {
+			Error::Process { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+			_ => StatusCode::UNPROCESSABLE_ENTITY,
+		}
+	}
+}
+
+/// A flow's nodes resolved into a directed acyclic graph, addressed by node index.
+pub struct Graph {
+	pub ids: Vec<String>,
+	/// Upstream nodes of each node, in connection order.
+	pub inputs: Vec<Vec<usize>>,
+	/// Downstream nodes of each node.
+	pub outputs: Vec<Vec<usize>>,
+	/// All nodes in topological order.
+	pub order: Vec<usize>,
+}
+
+impl Graph {
+	/// Nodes without downstream connections, which make up the outputs of the flow.
+	pub fn sinks(&self) -> impl Iterator<Item = usize> + '_ {
+		self.order
+			.iter()
+			.copied()
+			.filter(|&i| self.outputs[i].is_empty())
+	}
+}
+
+impl Flow {
+	pub fn graph(&self) -> Result<Graph, Error> {
+		let ids: Vec<String> = self
+			.nodes
+			.iter()
+			.enumerate()
+			.map(|(i, n)| n.id(i))
+			.collect();
+
+		let mut index = HashMap::with_capacity(ids.len());
+		for (i, id) in ids.iter().enumerate() {
+			if index.insert(id.as_str(), i).is_some() {
+				return Err(Error::DuplicateId(id.clone()));
+			}
+		}
+
+		let mut inputs = vec![Vec::new(); ids.len()];
+		let mut outputs = vec![Vec::new(); ids.len()];
+
+		if self.connections.is_empty() {
+			for i in 1..ids.len() {
+				inputs[i].push(i - 1);
+				outputs[i - 1].push(i);
+			}
+		} else {
+			for Connection { from, to } in &self.connections {
+				let from = *index
+					.get(from.as_str())
+					.ok_or_else(|| Error::UnknownId(from.clone()))?;
+				let to = *index
+					.get(to.as_str())
+					.ok_or_else(|| Error::UnknownId(to.clone()))?;
+
+				inputs[to].push(from);
+				outputs[from].push(to);
+			}
+		}
+
+		// Kahn's algorithm
+		let mut remaining: Vec<usize> = inputs.iter().map(Vec::len).collect();
+		let mut order: Vec<usize> = (0..ids.len()).filter(|&i| remaining[i] == 0).collect();
+		let mut next = 0;
+		while let Some(&i) = order.get(next) {
+			next += 1;
+			for &o in &outputs[i] {
+				remaining[o] -= 1;
+				if remaining[o] == 0 {
+					order.push(o);
+				}
+			}
+		}
+
+		if order.len() < ids.len() {
+			let sorted: HashSet<usize> = order.into_iter().collect();
+			let i = (0..ids.len())
+				.find(|i| !sorted.contains(i))
+				.unwrap_or_default();
+			return Err(Error::Cycle(ids[i].clone()));
+		}
+
+		Ok(Graph {
+			ids,
+			inputs,
+			outputs,
+			order,
+		})
+	}
+
+	/// Runs the flow, starting each node as soon as all of its inputs are available.
+	///
+	/// Returns the payloads produced by the flow's output nodes, keyed by node id. Each node that
+	/// ran is traced in `traces`, including the one that failed and any still running then.
+	pub async fn run(
+		&self,
+		name: &str,
+		nodes: &Nodes,
+		traces: &mut Vec<NodeTrace>,
+	) -> Result<Outputs, Error> {
+		let graph = self.graph()?;
+
+		if let Some(node) = self.nodes.iter().find(|n| !nodes.contains(&n.r#type)) {
+			return Err(Error::UnknownNode(node.r#type.clone()));
+		}
+
+		let mut results: Vec<Option<Option<Any>>> = vec![None; self.nodes.len()];
+		// Feed entries each node produced, counted once for all of its downstream nodes.
+		let mut entries: Vec<Option<usize>> = vec![None; self.nodes.len()];
+		let mut remaining: Vec<usize> = graph.inputs.iter().map(Vec::len).collect();
+		let mut running: FuturesUnordered<_> = graph
+			.order
+			.iter()
+			.filter(|&&i| remaining[i] == 0)
+			.map(|&i| {
+				let context = FlowContext {
+					flow: name.to_string(),
+					node: graph.ids[i].clone(),
+				};
+				run_node(i, context, nodes, &self.nodes[i], Vec::new(), None)
+			})
+			.collect();
+
+		while let Some((i, result, trace)) = running.next().await {
+			entries[i] = trace.entries_out;
+			traces.push(trace);
+			let payload = match result {
+				Ok(payload) => payload,
+				Err(err) => {
+					// Nodes already running are still traced, downstream ones never start.
+					while let Some((_, _, trace)) = running.next().await {
+						traces.push(trace);
+					}
+					return Err(err);
+				}
+			};
+			results[i] = Some(payload);
+
+			for &o in &graph.outputs[i] {
+				remaining[o] -= 1;
+				if remaining[o] == 0 {
+					let upstream: Vec<usize> = graph.inputs[o]
+						.iter()
+						.copied()
+						.filter(|&j| matches!(results[j], Some(Some(_))))
+						.collect();
+					// Only counted if every input is a feed.
+					let entries_in = if upstream.is_empty() {
+						None
+					} else {
+						upstream.iter().map(|&j| entries[j]).sum()
+					};
+					let inputs = upstream
+						.iter()
+						.filter_map(|&j| results[j].clone().flatten())
+						.collect();
+					let context = FlowContext {
+						flow: name.to_string(),
+						node: graph.ids[o].clone(),
+					};
+					running.push(run_node(
+						o,
+						context,
+						nodes,
+						&self.nodes[o],
+						inputs,
+						entries_in,
+					));
+				}
+			}
+		}
+
+		Ok(graph
+			.sinks()
+			.map(|i| (graph.ids[i].clone(), results[i].take().flatten()))
+			.collect())
+	}
+}
+
+async fn run_node(
+	index: usize,
+	context: FlowContext,
+	nodes: &Nodes,
+	node: &NodeOptions,
+	inputs: Vec<Any>,
+	entries_in: Option<usize>,
+) -> (usize, Result<Option<Any>, Error>, NodeTrace) {
+	let id = context.node.clone();
+	let started_at = Utc::now();
+	let start = Instant::now();
+
+	let (result, websub) = match process_node(context, nodes, node, inputs).await {
+		Ok((payload, websub)) => (Ok(payload), websub),
+		Err(err) => (Err(err), None),
+	};
+
+	let trace = NodeTrace {
+		id,
+		r#type: node.r#type.clone(),
+		started_at,
+		duration_ms: u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+		entries_in,
+		entries_out: result
+			.as_ref()
+			.ok()
+			.and_then(Option::as_ref)
+			.and_then(run::entries),
+		websub,
+		error: result.as_ref().err().map(ToString::to_string),
+	};
+	(index, result, trace)
+}
+
+/// Sends the inputs to an instance of the node, returning its output and the WebSub state it
+/// reported, if any.
+async fn process_node(
+	context: FlowContext,
+	nodes: &Nodes,
+	node: &NodeOptions,
+	mut inputs: Vec<Any>,
+) -> Result<(Option<Any>, Option<String>), Error> {
+	// Instances are picked per request, the one picked when the flow started may be gone.
+	let Some(lease) = nodes.pick(&node.r#type) else {
+		return Err(Error::UnknownNode(node.r#type.clone()));
+	};
+
+	let (payload, payloads) = if inputs.len() > 1 {
+		(None, inputs)
+	} else {
+		(inputs.pop(), Vec::new())
+	};
+
+	info!(
+		"Sending request to {} node ({}) at {}",
+		node.r#type, context.node, lease.node.address
+	);
+	let id = context.node.clone();
+	lease
+		.node
+		.process(ProcessRequest {
+			payload,
+			options: node.options(),
+			payloads,
+			context: Some(context),
+		})
+		.await
+		.map(|res| {
+			let websub = res
+				.metadata()
+				.get(X_WEBSUB)
+				.and_then(|v| v.to_str().ok())
+				.map(ToString::to_string);
+			(res.into_inner().payload, websub)
+		})
+		.map_err(|source| Error::Process { id, source })
 }
 
-// TODO: Unused, might make this a thing again in the future
-#[derive(Serialize, Deserialize, Default)]
-struct FlowBuilder {
-	// nodes: Vec<NodeOptions>,
+#[cfg(test)]
+mod tests {
+	use rssflow_service::proto::node::NodeMeta;
+	use serde_json::json;
+
+	use super::{Error, Flow};
+	use crate::registry::Nodes;
+
+	fn flow(value: serde_json::Value) -> Flow {
+		serde_json::from_value(value).unwrap()
+	}
+
+	#[test]
+	fn chains_without_connections() {
+		let graph = flow(json!({
+			"nodes": [{ "type": "Fetch" }, { "type": "Filter" }, { "type": "Seen" }]
+		}))
+		.graph()
+		.unwrap();
+
+		assert_eq!(graph.ids, ["0", "1", "2"]);
+		assert_eq!(graph.order, [0, 1, 2]);
+		assert_eq!(graph.inputs, [vec![], vec![0], vec![1]]);
+		assert_eq!(graph.sinks().collect::<Vec<_>>(), [2]);
+	}
+
+	#[test]
+	fn cycle() {
+		let result = flow(json!({
+			"nodes": [
+				{ "id": "a", "type": "Fetch" },
+				{ "id": "b", "type": "Filter" },
+				{ "id": "c", "type": "Filter" }
+			],
+			"connections": [
+				{ "from": "a", "to": "b" },
+				{ "from": "b", "to": "c" },
+				{ "from": "c", "to": "b" }
+			]
+		}))
+		.graph();
+
+		assert!(matches!(result, Err(Error::Cycle(id)) if id == "b"));
+	}
+
+	#[test]
+	fn duplicate_id() {
+		let result = flow(json!({
+			"nodes": [{ "id": "a", "type": "Fetch" }, { "id": "a", "type": "Fetch" }]
+		}))
+		.graph();
+
+		assert!(matches!(result, Err(Error::DuplicateId(id)) if id == "a"));
+	}
+
+	#[test]
+	fn unknown_id() {
+		let result = flow(json!({
+			"nodes": [{ "id": "a", "type": "Fetch" }],
+			"connections": [{ "from": "a", "to": "b" }]
+		}))
+		.graph();
+
+		assert!(matches!(result, Err(Error::UnknownId(id)) if id == "b"));
+	}
+
+	#[test]
+	fn fan_out_fan_in() {
+		let graph = flow(json!({
+			"nodes": [
+				{ "id": "merge", "type": "Merge" },
+				{ "id": "fetch", "type": "Fetch" },
+				{ "id": "left", "type": "Filter" },
+				{ "id": "right", "type": "Filter" }
+			],
+			"connections": [
+				{ "from": "fetch", "to": "left" },
+				{ "from": "fetch", "to": "right" },
+				{ "from": "right", "to": "merge" },
+				{ "from": "left", "to": "merge" }
+			]
+		}))
+		.graph()
+		.unwrap();
+
+		let position = |i: usize| graph.order.iter().position(|&o| o == i).unwrap();
+		assert_eq!(graph.order[0], 1);
+		assert!(position(1) < position(2) && position(1) < position(3));
+		assert!(position(2) < position(0) && position(3) < position(0));
+
+		// The merge node gets both payloads, in connection order.
+		assert_eq!(graph.inputs[0], [3, 2]);
+		assert_eq!(graph.outputs[1], [2, 3]);
+		assert_eq!(graph.sinks().collect::<Vec<_>>(), [0]);
+	}
+
+	#[tokio::test]
+	async fn unknown_node() {
+		let flow = flow(json!({ "nodes": [{ "type": "Missing" }] }));
+
+		let result = flow.run("test", &Nodes::default(), &mut Vec::new()).await;
+		assert!(matches!(result, Err(Error::UnknownNode(name)) if name == "Missing"));
+	}
+
+	#[tokio::test]
+	async fn failing_nodes_traced() {
+		let nodes = Nodes::default();
+		// Nothing listens on port 1, so every request fails.
+		nodes.insert(NodeMeta {
+			node_name: "Fetch".to_string(),
+			address: "http://127.0.0.1:1".to_string(),
+		});
+		nodes.insert(NodeMeta {
+			node_name: "Merge".to_string(),
+			address: "http://127.0.0.1:1".to_string(),
+		});
+		let flow = flow(json!({
+			"nodes": [
+				{ "id": "left", "type": "Fetch" },
+				{ "id": "right", "type": "Fetch" },
+				{ "id": "merge", "type": "Merge" }
+			],
+			"connections": [
+				{ "from": "left", "to": "merge" },
+				{ "from": "right", "to": "merge" }
+			]
+		}));
+
+		let mut traces = Vec::new();
+		let result = flow.run("test", &nodes, &mut traces).await;
+		assert!(matches!(result, Err(Error::Process { .. })));
+
+		// Both sources were running when the first one failed, the merge never started.
+		let mut ids: Vec<&str> = traces.iter().map(|t| t.id.as_str()).collect();
+		ids.sort_unstable();
+		assert_eq!(ids, ["left", "right"]);
+		for trace in &traces {
+			assert!(trace.error.is_some(), "{trace:?}");
+			assert_eq!(trace.entries_out, None);
+		}
+	}
 }
-//
-// impl FlowBuilder {
-// 	pub fn node(mut self, node: impl Into<Node>) -> Self {
-// 		self.nodes.push(node.into());
-// 		self
-// 	}
-//
-// 	pub fn simple(mut self) -> Self {
-// 		self.connections.clear();
-// 		for i in 0..self.nodes.len() {
-// 			if i > 0 {
-// 				self.connections
-// 					.push(Connection(Port(i - 1, 0), Port(i, 0)));
-// 			}
-// 		}
-//
-// 		self
-// 	}
-//
-// 	pub fn build(mut self) -> Flow {
-// 		// TODO: Improve this code once multiple-input nodes actually exist.
-// 		// TODO: Collect all unconnected inputs/outputs into flow inputs/outputs
-// 		let inputs: Box<[Arc<IO>]> = self
-// 			.nodes
-// 			.first()
-// 			.iter()
-// 			.flat_map(|n| n.input_types())
-// 			.map(|d| Arc::new(IO::new(*d)))
-// 			.collect();
-//
-// 		let outputs: Box<[Arc<IO>]> = self
-// 			.nodes
-// 			.last()
-// 			.iter()
-// 			.flat_map(|n| n.output_types())
-// 			.map(|d| Arc::new(IO::new(*d)))
-// 			.collect();
-//
-// 		if !self.nodes.is_empty() {
-// 			if let Some(first) = self.nodes.first_mut() {
-// 				for (i, input) in inputs.iter().enumerate() {
-// 					first.set_input(i, input.clone());
-// 				}
-// 			}
-// 			if let Some(last) = self.nodes.last_mut() {
-// 				for (i, output) in outputs.iter().enumerate() {
-// 					last.set_output(i, output.clone());
-// 				}
-// 			}
-//
-// 			if self.connections.is_empty() {
-// 				self = self.simple();
-// 			}
-// 			let mut port_map = HashMap::new();
-// 			for Connection(from, to) in self.connections {
-// 				if let Some(from_n) = self.nodes.get_mut(from.0) {
-// 					if let Some(kind) = from_n.output_types().get(from.1).copied() {
-// 						// NOTE: If multiple outputs are connected to the same input, this will only correctly assign the last one.
-// 						let io = port_map.entry(from).or_insert_with(|| {
-// 							let io = Arc::new(IO::new(kind));
-// 							from_n.set_output(from.1, io.clone());
-// 							io
-// 						});
-//
-// 						if let Some(to_n) = self.nodes.get_mut(to.0) {
-// 							to_n.connect(io.clone(), to.1);
-// 						}
-// 					}
-// 				}
-// 			}
-// 		}
-//
-// 		Flow {
-// 			nodes: Mutex::new(self.nodes),
-// 			inputs,
-// 			outputs,
-// 			subscriptions: parking_lot::Mutex::default(),
-// 		}
-// 	}
-// }
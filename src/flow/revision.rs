@@ -0,0 +1,137 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{PgConnection, PgPool};
+
+/// A saved version of a flow, without its content.
+#[derive(Serialize, Debug)]
+pub struct Revision {
+	pub revision: i32,
+	pub author: Option<String>,
+	pub message: Option<String>,
+	pub created_at: DateTime<Utc>,
+}
+
+/// Stores `content` as the next revision of the flow `name`, returning its number.
+///
+/// Locks the flow's row for the rest of the transaction, so concurrent changes get consecutive
+/// numbers.
+pub async fn record(
+	conn: &mut PgConnection,
+	name: &str,
+	content: &Value,
+	author: Option<&str>,
+	message: Option<&str>,
+) -> Result<i32, sqlx::Error> {
+	sqlx::query_scalar!("SELECT name FROM flows WHERE name = $1 FOR UPDATE", name)
+		.fetch_one(&mut *conn)
+		.await?;
+	sqlx::query_scalar!(
+		"INSERT INTO flow_revisions (flow, revision, content, author, message) SELECT $1, COALESCE(MAX(revision), 0) + 1, $2, $3, $4 FROM flow_revisions WHERE flow = $1 RETURNING revision",
+		name,
+		content,
+		author,
+		message
+	)
+	.fetch_one(conn)
+	.await
+}
+
+/// Revisions of the flow `name`, newest first.
+pub async fn list(pool: &PgPool, name: &str) -> Result<Vec<Revision>, sqlx::Error> {
+	let records = sqlx::query!(
+		"SELECT revision, author, message, created_at FROM flow_revisions WHERE flow = $1 ORDER BY revision DESC",
+		name
+	)
+	.fetch_all(pool)
+	.await?;
+
+	Ok(records
+		.into_iter()
+		.map(|r| Revision {
+			revision: r.revision,
+			author: r.author,
+			message: r.message,
+			created_at: r.created_at,
+		})
+		.collect())
+}
+
+/// Revision `revision` of the flow `name`, with its content.
+pub async fn get(
+	pool: &PgPool,
+	name: &str,
+	revision: i32,
+) -> Result<Option<(Revision, Value)>, sqlx::Error> {
+	let record = sqlx::query!(
+		"SELECT revision, author, message, created_at, content FROM flow_revisions WHERE flow = $1 AND revision = $2",
+		name,
+		revision
+	)
+	.fetch_optional(pool)
+	.await?;
+
+	Ok(record.map(|r| {
+		let revision = Revision {
+			revision: r.revision,
+			author: r.author,
+			message: r.message,
+			created_at: r.created_at,
+		};
+		(revision, r.content)
+	}))
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+	use sqlx::PgPool;
+
+	use super::{get, list, record};
+
+	#[sqlx::test]
+	async fn record_list_get(pool: PgPool) {
+		let mut conn = pool.acquire().await.unwrap();
+		sqlx::query!(
+			"INSERT INTO flows (name, content) VALUES ($1, $2)",
+			"news",
+			json!({ "nodes": [] })
+		)
+		.execute(&mut *conn)
+		.await
+		.unwrap();
+
+		let first = record(&mut conn, "news", &json!({ "nodes": [] }), None, None)
+			.await
+			.unwrap();
+		let second = record(
+			&mut conn,
+			"news",
+			&json!({ "nodes": [], "interval": 60 }),
+			Some("alice"),
+			Some("Schedule hourly"),
+		)
+		.await
+		.unwrap();
+		assert_eq!((first, second), (1, 2));
+
+		let revisions = list(&pool, "news").await.unwrap();
+		let numbers: Vec<i32> = revisions.iter().map(|r| r.revision).collect();
+		assert_eq!(numbers, [2, 1]);
+		assert_eq!(revisions[0].author.as_deref(), Some("alice"));
+		assert_eq!(revisions[0].message.as_deref(), Some("Schedule hourly"));
+
+		let (revision, content) = get(&pool, "news", 2).await.unwrap().unwrap();
+		assert_eq!(revision.revision, 2);
+		assert_eq!(content, json!({ "nodes": [], "interval": 60 }));
+		assert!(get(&pool, "news", 3).await.unwrap().is_none());
+		assert!(list(&pool, "other").await.unwrap().is_empty());
+	}
+
+	#[sqlx::test]
+	async fn unknown_flow(pool: PgPool) {
+		let mut conn = pool.acquire().await.unwrap();
+		let result = record(&mut conn, "missing", &json!({ "nodes": [] }), None, None).await;
+		assert!(matches!(result, Err(sqlx::Error::RowNotFound)));
+	}
+}
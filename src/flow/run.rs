@@ -0,0 +1,288 @@
+use chrono::{DateTime, Utc};
+use prost_types::Any;
+use rssflow_service::proto::feed::Feed;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+/// Runs kept per flow and trigger, older ones are dropped as new ones are saved.
+const HISTORY: i64 = 100;
+/// Runs started by requests kept per flow, so busy feeds don't crowd out the other runs.
+const REQUEST_HISTORY: i64 = 10;
+
+/// What started a run.
+#[derive(Debug, Clone, Copy)]
+pub enum Trigger {
+	/// The flow's feed was requested.
+	Request,
+	/// The flow's interval elapsed.
+	Schedule,
+	/// A WebSub hub pushed to one of the flow's sources.
+	Push,
+}
+
+impl Trigger {
+	fn as_str(self) -> &'static str {
+		match self {
+			Trigger::Request => "request",
+			Trigger::Schedule => "schedule",
+			Trigger::Push => "push",
+		}
+	}
+
+	fn history(self) -> i64 {
+		match self {
+			Trigger::Request => REQUEST_HISTORY,
+			Trigger::Schedule | Trigger::Push => HISTORY,
+		}
+	}
+}
+
+/// How a single node fared during a run.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NodeTrace {
+	pub id: String,
+	#[serde(rename = "type")]
+	pub r#type: String,
+	pub started_at: DateTime<Utc>,
+	pub duration_ms: u64,
+	/// Feed entries the node got, if its inputs were feeds.
+	pub entries_in: Option<usize>,
+	/// Feed entries the node produced, if its output was a feed.
+	pub entries_out: Option<usize>,
+	/// WebSub state the node reported, e.g. `subscribed`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub websub: Option<String>,
+	pub error: Option<String>,
+}
+
+/// Number of feed entries in `payload`, if it's a feed.
+pub fn entries(payload: &Any) -> Option<usize> {
+	Feed::try_from(payload.clone())
+		.ok()
+		.map(|feed| feed.entries.len())
+}
+
+/// A recorded run, without its node traces.
+#[derive(Serialize, Debug)]
+pub struct Run {
+	pub id: i64,
+	pub flow: String,
+	pub trigger: String,
+	/// `success` or `failure`.
+	pub status: String,
+	pub error: Option<String>,
+	pub started_at: DateTime<Utc>,
+	pub finished_at: DateTime<Utc>,
+}
+
+/// Records a run of the flow `name` that just finished, returning its id.
+pub async fn save(
+	pool: &PgPool,
+	name: &str,
+	trigger: Trigger,
+	started_at: DateTime<Utc>,
+	error: Option<&str>,
+	nodes: &[NodeTrace],
+) -> Result<i64, sqlx::Error> {
+	let status = if error.is_some() {
+		"failure"
+	} else {
+		"success"
+	};
+	let nodes = serde_json::to_value(nodes).map_err(|err| sqlx::Error::Encode(err.into()))?;
+
+	let mut tx = pool.begin().await?;
+	let id = sqlx::query_scalar!(
+		"INSERT INTO flow_runs (flow, trigger, status, error, started_at, nodes) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+		name,
+		trigger.as_str(),
+		status,
+		error,
+		started_at,
+		nodes
+	)
+	.fetch_one(&mut *tx)
+	.await?;
+	sqlx::query!(
+		"DELETE FROM flow_runs WHERE flow = $1 AND trigger = $2 AND id NOT IN (SELECT id FROM flow_runs WHERE flow = $1 AND trigger = $2 ORDER BY id DESC LIMIT $3)",
+		name,
+		trigger.as_str(),
+		trigger.history()
+	)
+	.execute(&mut *tx)
+	.await?;
+	tx.commit().await?;
+
+	Ok(id)
+}
+
+/// Recorded runs of the flow `name`, newest first.
+pub async fn list(pool: &PgPool, name: &str) -> Result<Vec<Run>, sqlx::Error> {
+	let records = sqlx::query!(
+		"SELECT id, flow, trigger, status, error, started_at, finished_at FROM flow_runs WHERE flow = $1 ORDER BY id DESC",
+		name
+	)
+	.fetch_all(pool)
+	.await?;
+
+	Ok(records
+		.into_iter()
+		.map(|r| Run {
+			id: r.id,
+			flow: r.flow,
+			trigger: r.trigger,
+			status: r.status,
+			error: r.error,
+			started_at: r.started_at,
+			finished_at: r.finished_at,
+		})
+		.collect())
+}
+
+/// Run `id`, with its node traces.
+pub async fn get(pool: &PgPool, id: i64) -> Result<Option<(Run, Vec<NodeTrace>)>, sqlx::Error> {
+	let Some(record) = sqlx::query!(
+		"SELECT id, flow, trigger, status, error, started_at, finished_at, nodes FROM flow_runs WHERE id = $1",
+		id
+	)
+	.fetch_optional(pool)
+	.await?
+	else {
+		return Ok(None);
+	};
+
+	let nodes =
+		serde_json::from_value(record.nodes).map_err(|err| sqlx::Error::Decode(err.into()))?;
+	let run = Run {
+		id: record.id,
+		flow: record.flow,
+		trigger: record.trigger,
+		status: record.status,
+		error: record.error,
+		started_at: record.started_at,
+		finished_at: record.finished_at,
+	};
+	Ok(Some((run, nodes)))
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::Utc;
+	use serde_json::json;
+	use sqlx::PgPool;
+
+	use super::{HISTORY, NodeTrace, REQUEST_HISTORY, Trigger, get, list, save};
+
+	fn trace(error: Option<&str>) -> NodeTrace {
+		NodeTrace {
+			id: "fetch".to_string(),
+			r#type: "Fetch".to_string(),
+			started_at: Utc::now(),
+			duration_ms: 12,
+			entries_in: None,
+			entries_out: error.is_none().then_some(3),
+			websub: None,
+			error: error.map(ToString::to_string),
+		}
+	}
+
+	async fn insert_flow(pool: &PgPool, name: &str) {
+		sqlx::query!(
+			"INSERT INTO flows (name, content) VALUES ($1, $2)",
+			name,
+			json!({ "nodes": [] })
+		)
+		.execute(pool)
+		.await
+		.unwrap();
+	}
+
+	#[sqlx::test]
+	async fn save_list_get(pool: PgPool) {
+		insert_flow(&pool, "news").await;
+
+		let ok = save(
+			&pool,
+			"news",
+			Trigger::Schedule,
+			Utc::now(),
+			None,
+			&[trace(None)],
+		)
+		.await
+		.unwrap();
+		let failed = save(
+			&pool,
+			"news",
+			Trigger::Request,
+			Utc::now(),
+			Some("Node fetch failed"),
+			&[trace(Some("unreachable"))],
+		)
+		.await
+		.unwrap();
+
+		let runs = list(&pool, "news").await.unwrap();
+		let ids: Vec<i64> = runs.iter().map(|r| r.id).collect();
+		assert_eq!(ids, [failed, ok]);
+		assert_eq!(runs[0].status, "failure");
+		assert_eq!(runs[0].trigger, "request");
+		assert_eq!(runs[1].status, "success");
+		assert_eq!(runs[1].trigger, "schedule");
+
+		let (run, nodes) = get(&pool, failed).await.unwrap().unwrap();
+		assert_eq!(run.error.as_deref(), Some("Node fetch failed"));
+		assert_eq!(nodes.len(), 1);
+		assert_eq!(nodes[0].error.as_deref(), Some("unreachable"));
+		assert_eq!(nodes[0].entries_out, None);
+
+		let (_, nodes) = get(&pool, ok).await.unwrap().unwrap();
+		assert_eq!(nodes[0].entries_out, Some(3));
+		assert!(get(&pool, failed + 1).await.unwrap().is_none());
+	}
+
+	#[sqlx::test]
+	async fn prunes_to_history(pool: PgPool) {
+		insert_flow(&pool, "news").await;
+		insert_flow(&pool, "other").await;
+
+		let other = save(&pool, "other", Trigger::Push, Utc::now(), None, &[])
+			.await
+			.unwrap();
+		let mut ids = Vec::new();
+		for _ in 0..=HISTORY {
+			ids.push(
+				save(&pool, "news", Trigger::Schedule, Utc::now(), None, &[])
+					.await
+					.unwrap(),
+			);
+		}
+
+		let runs = list(&pool, "news").await.unwrap();
+		assert_eq!(runs.len(), HISTORY as usize);
+		assert_eq!(runs[0].id, ids[ids.len() - 1]);
+		assert_eq!(runs[runs.len() - 1].id, ids[1]);
+		assert!(get(&pool, ids[0]).await.unwrap().is_none());
+		// Other flows keep their runs.
+		assert!(get(&pool, other).await.unwrap().is_some());
+	}
+
+	#[sqlx::test]
+	async fn prunes_requests_separately(pool: PgPool) {
+		insert_flow(&pool, "news").await;
+
+		let scheduled = save(&pool, "news", Trigger::Schedule, Utc::now(), None, &[])
+			.await
+			.unwrap();
+		for _ in 0..=REQUEST_HISTORY {
+			save(&pool, "news", Trigger::Request, Utc::now(), None, &[])
+				.await
+				.unwrap();
+		}
+
+		let runs = list(&pool, "news").await.unwrap();
+		let requests = runs.iter().filter(|r| r.trigger == "request").count();
+		assert_eq!(requests, REQUEST_HISTORY as usize);
+		assert_eq!(runs[runs.len() - 1].id, scheduled);
+	}
+}
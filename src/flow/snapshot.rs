@@ -0,0 +1,63 @@
+use std::collections::BTreeMap;
+
+use prost::Message;
+use prost_types::Any;
+use sqlx::PgPool;
+
+/// Payloads produced by a flow's output nodes, keyed by node id.
+pub type Outputs = BTreeMap<String, Option<Any>>;
+
+/// Replaces the stored outputs of the flow `name`.
+pub async fn save(pool: &PgPool, name: &str, outputs: &Outputs) -> Result<(), sqlx::Error> {
+	let mut tx = pool.begin().await?;
+
+	sqlx::query!("DELETE FROM snapshots WHERE flow = $1", name)
+		.execute(&mut *tx)
+		.await?;
+	for (output, payload) in outputs {
+		sqlx::query!(
+			"INSERT INTO snapshots (flow, output, payload) VALUES ($1, $2, $3)",
+			name,
+			output,
+			payload.as_ref().map(Message::encode_to_vec)
+		)
+		.execute(&mut *tx)
+		.await?;
+	}
+
+	tx.commit().await
+}
+
+/// The last stored outputs of the flow `name`, if it ran since it was last saved.
+pub async fn load(pool: &PgPool, name: &str) -> Result<Option<Outputs>, sqlx::Error> {
+	let records = sqlx::query!(
+		"SELECT output, payload FROM snapshots WHERE flow = $1",
+		name
+	)
+	.fetch_all(pool)
+	.await?;
+	if records.is_empty() {
+		return Ok(None);
+	}
+
+	records
+		.into_iter()
+		.map(|r| {
+			let payload = r
+				.payload
+				.map(|bytes| Any::decode(bytes.as_slice()))
+				.transpose()
+				.map_err(|err| sqlx::Error::Decode(err.into()))?;
+			Ok((r.output, payload))
+		})
+		.collect::<Result<_, _>>()
+		.map(Some)
+}
+
+/// Drops the stored outputs of the flow `name`, e.g. because it changed.
+pub async fn clear(pool: &PgPool, name: &str) -> Result<(), sqlx::Error> {
+	sqlx::query!("DELETE FROM snapshots WHERE flow = $1", name)
+		.execute(pool)
+		.await?;
+	Ok(())
+}
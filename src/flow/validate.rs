@@ -0,0 +1,175 @@
+use std::{collections::HashMap, str::FromStr};
+
+use rssflow_service::proto::{
+	feed::path::FieldPath,
+	node::{NodeDescription, OptionSchema, OptionType},
+};
+use serde::Serialize;
+
+use super::{Flow, Value};
+use crate::registry::Nodes;
+
+/// A problem with a flow, found before running it.
+#[derive(Serialize, Debug)]
+pub struct ValidationError {
+	/// Id of the offending node, absent for errors about the flow as a whole.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub node: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub option: Option<String>,
+	pub message: String,
+}
+
+fn check_type(value: &Value, schema: &OptionSchema) -> Result<(), String> {
+	match (schema.r#type(), value) {
+		(OptionType::String, Value::String(s))
+			if !schema.values.is_empty() && !schema.values.contains(s) =>
+		{
+			Err(format!("expected oneof [{}]", schema.values.join(", ")))
+		}
+		(OptionType::String, Value::String(_))
+		| (OptionType::Number, Value::Number(_))
+		| (OptionType::Bool, Value::Bool(_))
+		| (OptionType::Field, Value::Field(_) | Value::Number(_)) => Ok(()),
+		(OptionType::Field, Value::String(path)) => FieldPath::from_str(path)
+			.map(|_| ())
+			.map_err(|e| e.to_string()),
+		(r#type, _) => Err(format!("expected {}", r#type.as_str_name().to_lowercase())),
+	}
+}
+
+impl Flow {
+	/// Checks the flow's graph, and every node against the registry.
+	///
+	/// Options and inputs are only checked for nodes that describe them.
+	pub fn validate(
+		&self,
+		nodes: &Nodes,
+		descriptions: &HashMap<String, NodeDescription>,
+	) -> Vec<ValidationError> {
+		let mut errors = Vec::new();
+
+		let graph = match self.graph() {
+			Ok(graph) => Some(graph),
+			Err(err) => {
+				errors.push(ValidationError {
+					node: None,
+					option: None,
+					message: err.to_string(),
+				});
+				None
+			}
+		};
+		if self.interval == Some(0) {
+			errors.push(ValidationError {
+				node: None,
+				option: None,
+				message: "Interval must be at least one second".into(),
+			});
+		}
+
+		for (i, node) in self.nodes.iter().enumerate() {
+			let id = node.id(i);
+			let error = |option: Option<&str>, message: String| ValidationError {
+				node: Some(id.clone()),
+				option: option.map(ToString::to_string),
+				message,
+			};
+
+			if !nodes.contains(&node.r#type) {
+				errors.push(error(None, format!("No such node: {}", node.r#type)));
+				continue;
+			}
+			let Some(description) = descriptions.get(&node.r#type) else {
+				continue;
+			};
+
+			let inputs = graph.as_ref().map_or(0, |g| g.inputs[i].len());
+			if inputs > 1 && !description.multiple_inputs {
+				errors.push(error(None, "Node does not accept multiple inputs".into()));
+			}
+
+			for schema in &description.options {
+				match node.options.get(&schema.name) {
+					Some(value) => {
+						if let Err(message) = check_type(value, schema) {
+							errors.push(error(Some(&schema.name), message));
+						}
+					}
+					None if schema.required => {
+						errors.push(error(
+							Some(&schema.name),
+							"Required option is missing".into(),
+						));
+					}
+					None => {}
+				}
+			}
+
+			for name in node.options.keys() {
+				if !description.options.iter().any(|o| &o.name == name) {
+					errors.push(error(Some(name), "Unknown option".into()));
+				}
+			}
+		}
+
+		errors
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use rssflow_service::proto::node::{NodeDescription, NodeMeta};
+	use serde_json::json;
+
+	use crate::{flow::Flow, registry::Nodes};
+
+	fn errors(multiple_inputs: bool) -> Vec<(Option<String>, String)> {
+		let nodes = Nodes::default();
+		let mut descriptions = HashMap::new();
+		for (name, multiple_inputs) in [("Fetch", false), ("Sink", multiple_inputs)] {
+			nodes.insert(NodeMeta {
+				node_name: name.to_string(),
+				address: format!("http://{}:50051", name.to_lowercase()),
+			});
+			descriptions.insert(
+				name.to_string(),
+				NodeDescription {
+					multiple_inputs,
+					..NodeDescription::default()
+				},
+			);
+		}
+
+		let flow: Flow = serde_json::from_value(json!({
+			"nodes": [
+				{ "id": "a", "type": "Fetch" },
+				{ "id": "b", "type": "Fetch" },
+				{ "id": "sink", "type": "Sink" }
+			],
+			"connections": [
+				{ "from": "a", "to": "sink" },
+				{ "from": "b", "to": "sink" }
+			]
+		}))
+		.unwrap();
+		flow.validate(&nodes, &descriptions)
+			.into_iter()
+			.map(|e| (e.node, e.message))
+			.collect()
+	}
+
+	#[test]
+	fn multiple_inputs() {
+		assert_eq!(
+			errors(false),
+			[(
+				Some("sink".to_string()),
+				"Node does not accept multiple inputs".to_string()
+			)]
+		);
+		assert!(errors(true).is_empty());
+	}
+}
@@ -0,0 +1,137 @@
+use rssflow_service::proto::feed::{Feed, Link};
+use tracing::{info, warn};
+use url::Url;
+
+use crate::{
+	config::{Config, config},
+	route::feed::Format,
+};
+
+/// The URL flow `name` is published at in `format`, if rssflow is configured with a hub.
+fn topic(config: &Config, name: &str, format: Format) -> Option<Url> {
+	config.hub_url.as_ref()?;
+	let path = match format.extension() {
+		Some(extension) => format!("flow/{name}.{extension}"),
+		None => format!("flow/{name}"),
+	};
+	config.public_url.as_ref()?.join(&path).ok()
+}
+
+/// Replaces the hub and self links the flow's sources had with rssflow's own, the self link
+/// pointing at the feed in the format it's served in.
+pub fn advertise(feed: &mut Feed, name: &str, format: Format) {
+	advertise_with(config(), feed, name, format);
+}
+
+fn advertise_with(config: &Config, feed: &mut Feed, name: &str, format: Format) {
+	let (Some(topic), Some(hub)) = (topic(config, name, format), config.hub_url.as_ref()) else {
+		return;
+	};
+
+	feed.links.retain(|l| l.rel != "hub" && l.rel != "self");
+	for (href, rel) in [(hub.as_str(), "hub"), (topic.as_str(), "self")] {
+		feed.links.push(Link {
+			href: href.to_string(),
+			rel: rel.to_string(),
+			..Link::default()
+		});
+	}
+}
+
+/// Tells the hub that flow `name` has new entries, so it pushes the feed to its subscribers in
+/// every format.
+pub async fn notify(name: &str) {
+	let config = config();
+	let Some(hub) = config.hub_url.as_ref() else {
+		return;
+	};
+
+	let client = reqwest::Client::new();
+	for topic in Format::ALL
+		.into_iter()
+		.filter_map(|format| topic(config, name, format))
+	{
+		let result = client
+			.post(hub.as_str())
+			.form(&[("hub.mode", "publish"), ("hub.url", topic.as_str())])
+			.send()
+			.await
+			.and_then(reqwest::Response::error_for_status);
+		match result {
+			Ok(_) => info!("Notified {hub} about {topic}"),
+			Err(err) => warn!("Notifying {hub} about {topic} failed: {err}"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rssflow_service::proto::feed::{Feed, Link};
+	use url::Url;
+
+	use super::advertise_with;
+	use crate::{config::Config, route::feed::Format};
+
+	fn config(hub_url: Option<&str>) -> Config {
+		Config {
+			public_url: Some(Url::parse("https://rssflow.example.com/").unwrap()),
+			hub_url: hub_url.map(|url| Url::parse(url).unwrap()),
+			..Config::default()
+		}
+	}
+
+	fn feed() -> Feed {
+		let link = |href: &str, rel: &str| Link {
+			href: href.to_string(),
+			rel: rel.to_string(),
+			..Link::default()
+		};
+		Feed {
+			links: vec![
+				link("https://example.org/", "alternate"),
+				link("https://example.org/feed.atom", "self"),
+				link("https://hub.example.org/", "hub"),
+			],
+			..Feed::default()
+		}
+	}
+
+	fn links(feed: &Feed) -> Vec<(&str, &str)> {
+		feed.links
+			.iter()
+			.map(|l| (l.rel.as_str(), l.href.as_str()))
+			.collect()
+	}
+
+	#[test]
+	fn replaces_upstream_links() {
+		let config = config(Some("https://hub.rssflow.example.com/"));
+
+		let mut advertised = feed();
+		advertise_with(&config, &mut advertised, "news", Format::Atom);
+		assert_eq!(
+			links(&advertised),
+			[
+				("alternate", "https://example.org/"),
+				("hub", "https://hub.rssflow.example.com/"),
+				("self", "https://rssflow.example.com/flow/news"),
+			]
+		);
+
+		for (format, url) in [
+			(Format::Rss, "https://rssflow.example.com/flow/news.rss"),
+			(Format::Json, "https://rssflow.example.com/flow/news.json"),
+		] {
+			let mut advertised = feed();
+			advertise_with(&config, &mut advertised, "news", format);
+			assert_eq!(links(&advertised)[2], ("self", url));
+		}
+	}
+
+	#[test]
+	fn without_hub() {
+		let mut advertised = feed();
+		advertise_with(&config(None), &mut advertised, "news", Format::Json);
+		assert_eq!(links(&advertised), links(&feed()));
+	}
+}
@@ -3,36 +3,50 @@
 
 use std::{
 	collections::HashMap,
-	net::ToSocketAddrs,
 	ops::Deref,
-	str::FromStr,
-	sync::{Arc, Mutex},
+	sync::{Arc, Mutex, OnceLock},
 	time::Duration,
 };
 
-use anyhow::Context;
+use hickory_resolver::TokioAsyncResolver;
 use rssflow_service::{
-	NodeExt, proto,
-	proto::{
-		node::{NodeMeta, PingRequest, node_service_client::NodeServiceClient},
-		registry::node_registry_server::{NodeRegistry, NodeRegistryServer},
-	},
+	proto,
+	proto::{node::NodeDescription, registry::node_registry_server::NodeRegistryServer},
 };
 use runesys::Service;
-use tonic::transport::Endpoint;
+use sqlx::PgPool;
 
 mod app;
+mod config;
 mod flow;
+mod hub;
+mod registry;
 mod route;
-
-use crate::app::app;
+mod scheduler;
+mod stream;
+
+use crate::{
+	app::app,
+	registry::Nodes,
+	scheduler::{FlowLocks, InFlight},
+	stream::Streams,
+};
 
 // #[global_allocator]
 // static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 struct RSSFlowInner {
-	pub nodes: Mutex<HashMap<String, NodeMeta>>,
+	pub nodes: Nodes,
+	/// Option schemas of the known nodes, by node name.
+	pub descriptions: Mutex<HashMap<String, NodeDescription>>,
+	/// Set once the database is connected and migrated.
+	pub pool: OnceLock<PgPool>,
+	pub streams: Streams,
+	/// Held while a flow is refreshed.
+	pub flow_locks: FlowLocks,
+	/// Runs requests can join.
+	pub in_flight: InFlight,
 }
 
 #[derive(Service, Debug, Clone)]
@@ -49,77 +63,81 @@ impl Deref for RSSFlow {
 	}
 }
 
-#[tonic::async_trait]
-impl NodeRegistry for RSSFlow {}
-
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
 	runesys::tracing::init(&RSSFlow::INFO);
 
 	let svc = RSSFlow(Arc::new(RSSFlowInner {
-		nodes: Mutex::default(),
+		nodes: Nodes::default(),
+		descriptions: Mutex::default(),
+		pool: OnceLock::new(),
+		streams: Streams::default(),
+		flow_locks: FlowLocks::default(),
+		in_flight: InFlight::default(),
 	}));
 
 	let sd_task = {
 		let svc = svc.clone();
 		async move {
-			loop {
-				let resolve = "rssflow-headless:50051".to_socket_addrs();
-				let Ok(addrs) = resolve else {
-					tracing::error!("resolve failed: {resolve:?}");
-					tokio::time::sleep(Duration::from_secs(5)).await;
-					continue;
-				};
-
-				for addr in addrs {
-					let url = format!("http://{addr}");
-					tracing::debug!("trying to connect to {}", url);
-
-					let Ok(endpoint) =
-						Endpoint::from_str(&url).with_context(|| format!("create endpoint {url}"))
-					else {
-						continue;
-					};
-					let Ok(channel) = endpoint
-						.connect()
-						.await
-						.with_context(|| format!("connect to endpoint {url}"))
-					else {
-						continue;
-					};
-
-					#[cfg(not(feature = "telemetry"))]
-					let mut client = NodeServiceClient::new(channel);
-					#[cfg(feature = "telemetry")]
-					let mut client = NodeServiceClient::with_interceptor(
-						channel,
-						interceptor(telemetry::propagation::send_trace),
-					);
-
-					let Ok(response) = client.ping(PingRequest {}).await else {
-						continue;
-					};
-
-					if let Some(node) = response.into_inner().node {
-						let mut nodes = svc.nodes.lock().map_err(|_| {
-							runesys::error::Error::Config("poison lock".to_string())
-						})?;
-
-						nodes.insert(node.node_name.clone(), node);
-					}
-				}
+			let config = config::config();
+			let resolver = if config.srv.is_empty() {
+				None
+			} else {
+				TokioAsyncResolver::tokio_from_system_conf()
+					.inspect_err(|err| tracing::error!("create DNS resolver: {err}"))
+					.ok()
+			};
 
+			loop {
+				svc.discover_nodes(config, resolver.as_ref()).await;
 				tokio::time::sleep(Duration::from_secs(5)).await;
 			}
 		}
 	};
 
+	let health_task = {
+		let svc = svc.clone();
+		async move {
+			loop {
+				tokio::time::sleep(registry::HEARTBEAT_INTERVAL).await;
+				svc.check_nodes().await;
+			}
+		}
+	};
+
+	let scheduler_task = {
+		let svc = svc.clone();
+		async move {
+			let mut schedule = scheduler::Schedule::default();
+			loop {
+				tokio::time::sleep(scheduler::TICK).await;
+				if let Some(pool) = svc.pool.get() {
+					svc.run_scheduled(pool, &mut schedule).await;
+				}
+			}
+		}
+	};
+
 	let app = app(svc.clone());
 	let _ = svc
 		.builder()
-		.with_pg(|pool| async move { sqlx::migrate!().run(&pool).await })?
+		.with_pg({
+			let svc = svc.clone();
+			move |pool| {
+				let svc = svc.clone();
+				async move {
+					let migrated = sqlx::migrate!().run(&pool).await;
+					if migrated.is_ok() {
+						let _ = svc.pool.set(pool);
+					}
+					migrated
+				}
+			}
+		})?
 		.with_http(app.await?)
 		.with_task(sd_task)
+		.with_task(health_task)
+		.with_task(scheduler_task)
 		.run()
 		.await;
 	Ok(())
@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use futures::future::join_all;
+use rssflow_service::{
+	NodeExt,
+	channel::channels,
+	proto::{
+		node::{DescribeRequest, NodeDescription, NodeMeta},
+		registry::{
+			DeregisterRequest, DeregisterResponse, HeartbeatRequest, HeartbeatResponse,
+			RegisterRequest, RegisterResponse, RunFlowsRequest, RunFlowsResponse,
+			node_registry_server::NodeRegistry,
+		},
+	},
+};
+use tonic::{Request, Response, Status};
+use tonic_health::pb::{HealthCheckRequest, health_check_response::ServingStatus};
+use tracing::{error, info, instrument};
+
+pub use self::nodes::{Lease, Nodes};
+use crate::{RSSFlow, flow::run::Trigger};
+
+mod discovery;
+mod nodes;
+
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+/// Heartbeats a node may miss before it's removed.
+const MAX_MISSED_HEARTBEATS: u32 = 3;
+/// Health checks a node may fail in a row before it's removed.
+const MAX_FAILED_CHECKS: u32 = 3;
+
+async fn describe(node: &NodeMeta) -> Option<NodeDescription> {
+	let mut client = node.client().await.ok()?;
+	// Nodes that predate `Describe` are still usable, just not validated.
+	client
+		.describe(DescribeRequest {})
+		.await
+		.map(Response::into_inner)
+		.ok()
+}
+
+fn node_from(node: Option<NodeMeta>) -> Result<NodeMeta, Status> {
+	node.ok_or_else(|| Status::invalid_argument("node missing"))
+}
+
+/// Whether the node's gRPC health service reports it as serving.
+async fn probe(node: &NodeMeta) -> bool {
+	let Ok(mut client) = node.health().await else {
+		return false;
+	};
+
+	client
+		.check(HealthCheckRequest {
+			service: String::new(),
+		})
+		.await
+		.is_ok_and(|r| r.into_inner().status() == ServingStatus::Serving)
+}
+
+impl RSSFlow {
+	/// Adds a node instance, or marks a known one as alive.
+	pub async fn add_node(&self, node: NodeMeta) {
+		let described = self
+			.descriptions
+			.lock()
+			.unwrap()
+			.contains_key(&node.node_name);
+		if !described {
+			if let Some(description) = describe(&node).await {
+				self.descriptions
+					.lock()
+					.unwrap()
+					.insert(node.node_name.clone(), description);
+			}
+		}
+
+		if self.nodes.insert(node.clone()) {
+			info!("Added {} node at {}", node.node_name, node.address);
+		}
+	}
+
+	pub fn remove_node(&self, node: &NodeMeta) {
+		channels().evict(&node.address);
+		let Some(last) = self.nodes.remove(node) else {
+			return;
+		};
+
+		if last {
+			self.descriptions.lock().unwrap().remove(&node.node_name);
+		}
+		info!("Removed {} node at {}", node.node_name, node.address);
+	}
+
+	/// Removes node instances that stopped sending heartbeats or keep failing their health checks.
+	pub async fn check_nodes(&self) {
+		for node in self.nodes.stale(HEARTBEAT_INTERVAL * MAX_MISSED_HEARTBEATS) {
+			info!(
+				"{} node at {} missed its heartbeats",
+				node.node_name, node.address
+			);
+			self.remove_node(&node);
+		}
+
+		let instances: Vec<NodeMeta> = self.nodes.instances().into_iter().map(|(n, _)| n).collect();
+		let healthy = join_all(instances.iter().map(probe)).await;
+		for (node, healthy) in instances.iter().zip(healthy) {
+			let failed = self.nodes.record_check(node, healthy);
+			if failed == 0 {
+				continue;
+			}
+
+			info!(
+				"{} node at {} is unhealthy ({failed}/{MAX_FAILED_CHECKS})",
+				node.node_name, node.address
+			);
+			if failed >= MAX_FAILED_CHECKS {
+				self.remove_node(node);
+			}
+		}
+	}
+}
+
+#[tonic::async_trait]
+impl NodeRegistry for RSSFlow {
+	#[instrument(skip_all)]
+	async fn register(
+		&self,
+		request: Request<RegisterRequest>,
+	) -> Result<Response<RegisterResponse>, Status> {
+		let node = node_from(request.into_inner().node)?;
+		self.add_node(node).await;
+
+		Ok(Response::new(RegisterResponse {
+			heartbeat_interval: HEARTBEAT_INTERVAL.try_into().ok(),
+		}))
+	}
+
+	#[instrument(skip_all)]
+	async fn deregister(
+		&self,
+		request: Request<DeregisterRequest>,
+	) -> Result<Response<DeregisterResponse>, Status> {
+		let node = node_from(request.into_inner().node)?;
+		self.remove_node(&node);
+
+		Ok(Response::new(DeregisterResponse {}))
+	}
+
+	#[instrument(skip_all)]
+	async fn heartbeat(
+		&self,
+		request: Request<HeartbeatRequest>,
+	) -> Result<Response<HeartbeatResponse>, Status> {
+		let node = node_from(request.into_inner().node)?;
+
+		Ok(Response::new(HeartbeatResponse {
+			registered: self.nodes.touch(&node),
+		}))
+	}
+
+	#[instrument(skip_all)]
+	async fn run_flows(
+		&self,
+		request: Request<RunFlowsRequest>,
+	) -> Result<Response<RunFlowsResponse>, Status> {
+		let Some(pool) = self.pool.get().cloned() else {
+			return Err(Status::unavailable("database not ready"));
+		};
+
+		for name in request.into_inner().flows {
+			let svc = self.clone();
+			let pool = pool.clone();
+			tokio::spawn(async move {
+				match svc.refresh_stored(&pool, &name, Trigger::Push).await {
+					Ok(()) => info!("Re-ran `{name}`"),
+					Err(err) => error!("Running `{name}` failed: {err:#}"),
+				}
+			});
+		}
+
+		Ok(Response::new(RunFlowsResponse {}))
+	}
+}
@@ -0,0 +1,81 @@
+use std::net::ToSocketAddrs;
+
+use futures::future::join_all;
+use hickory_resolver::TokioAsyncResolver;
+use rssflow_service::{
+	NodeExt,
+	channel::channels,
+	proto::node::{NodeMeta, PingRequest},
+};
+use tracing::{debug, warn};
+
+use crate::{RSSFlow, config::Config};
+
+/// Asks the node at `address` who it is.
+async fn ping(address: String) -> Option<NodeMeta> {
+	debug!("trying to connect to {address}");
+	let node = NodeMeta {
+		address,
+		node_name: String::new(),
+	};
+
+	let mut client = node.client().await.ok()?;
+	match client.ping(PingRequest {}).await {
+		Ok(response) => response.into_inner().node,
+		Err(_) => {
+			channels().evict(&node.address);
+			None
+		}
+	}
+}
+
+fn resolve_host(host: &str) -> Vec<String> {
+	match host.to_socket_addrs() {
+		Ok(addrs) => addrs.map(|addr| format!("http://{addr}")).collect(),
+		Err(err) => {
+			warn!("Resolving {host} failed: {err}");
+			Vec::new()
+		}
+	}
+}
+
+async fn resolve_srv(resolver: &TokioAsyncResolver, name: &str) -> Vec<String> {
+	match resolver.srv_lookup(name).await {
+		Ok(lookup) => lookup
+			.iter()
+			.map(|srv| {
+				let target = srv.target().to_utf8();
+				format!("http://{}:{}", target.trim_end_matches('.'), srv.port())
+			})
+			.collect(),
+		Err(err) => {
+			warn!("Resolving SRV record {name} failed: {err}");
+			Vec::new()
+		}
+	}
+}
+
+impl RSSFlow {
+	/// Adds every node reachable through the configured endpoints, host names and SRV records.
+	///
+	/// Nodes found this way are kept alive by being found again, like a heartbeat.
+	pub async fn discover_nodes(&self, config: &Config, resolver: Option<&TokioAsyncResolver>) {
+		let mut addresses = config.nodes.clone();
+		for host in &config.hosts {
+			addresses.extend(resolve_host(host));
+		}
+		if let Some(resolver) = resolver {
+			for name in &config.srv {
+				addresses.extend(resolve_srv(resolver, name).await);
+			}
+		}
+
+		addresses.sort_unstable();
+		addresses.dedup();
+
+		let nodes = join_all(addresses.into_iter().map(ping)).await;
+		for node in nodes.into_iter().flatten() {
+			self.add_node(node).await;
+		}
+	}
+}
@@ -0,0 +1,208 @@
+use std::{
+	collections::HashMap,
+	sync::{
+		Arc, Mutex,
+		atomic::{AtomicUsize, Ordering},
+	},
+	time::{Duration, Instant},
+};
+
+use rssflow_service::proto::node::NodeMeta;
+
+/// A running instance of a node.
+#[derive(Debug)]
+struct Instance {
+	meta: NodeMeta,
+	last_seen: Instant,
+	in_flight: Arc<AtomicUsize>,
+	/// Health checks failed in a row.
+	failed_checks: u32,
+}
+
+/// All instances of a node type.
+#[derive(Debug, Default)]
+struct Pool {
+	instances: Vec<Instance>,
+	/// Where the search for the next instance starts, so ties are broken round-robin.
+	next: usize,
+}
+
+/// An instance picked to process a request, counted as in flight until dropped.
+pub struct Lease {
+	pub node: NodeMeta,
+	in_flight: Arc<AtomicUsize>,
+}
+
+impl Drop for Lease {
+	fn drop(&mut self) {
+		self.in_flight.fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
+/// The known node instances, by node type.
+#[derive(Debug, Default)]
+pub struct Nodes(Mutex<HashMap<String, Pool>>);
+
+impl Nodes {
+	pub fn contains(&self, node_name: &str) -> bool {
+		self.0.lock().unwrap().contains_key(node_name)
+	}
+
+	/// Picks the instance of `node_name` with the fewest requests in flight.
+	pub fn pick(&self, node_name: &str) -> Option<Lease> {
+		let mut nodes = self.0.lock().unwrap();
+		let pool = nodes.get_mut(node_name)?;
+
+		let len = pool.instances.len();
+		let index = (0..len)
+			.map(|i| (pool.next + i) % len)
+			.min_by_key(|&i| pool.instances[i].in_flight.load(Ordering::Relaxed))?;
+		pool.next = (index + 1) % len;
+
+		let instance = &pool.instances[index];
+		instance.in_flight.fetch_add(1, Ordering::Relaxed);
+		Some(Lease {
+			node: instance.meta.clone(),
+			in_flight: instance.in_flight.clone(),
+		})
+	}
+
+	/// Adds an instance, or marks a known one as alive. Returns whether it was new.
+	pub fn insert(&self, node: NodeMeta) -> bool {
+		let mut nodes = self.0.lock().unwrap();
+		let pool = nodes.entry(node.node_name.clone()).or_default();
+
+		if let Some(instance) = pool.instances.iter_mut().find(|i| i.meta == node) {
+			instance.last_seen = Instant::now();
+			return false;
+		}
+
+		pool.instances.push(Instance {
+			meta: node,
+			last_seen: Instant::now(),
+			in_flight: Arc::default(),
+			failed_checks: 0,
+		});
+		true
+	}
+
+	/// Marks an instance as alive. Returns whether it's known.
+	pub fn touch(&self, node: &NodeMeta) -> bool {
+		let mut nodes = self.0.lock().unwrap();
+		let instance = nodes
+			.get_mut(&node.node_name)
+			.and_then(|p| p.instances.iter_mut().find(|i| &i.meta == node));
+
+		match instance {
+			Some(instance) => {
+				instance.last_seen = Instant::now();
+				true
+			}
+			None => false,
+		}
+	}
+
+	/// Records the outcome of a health check, returning how many checks the instance has failed
+	/// in a row.
+	pub fn record_check(&self, node: &NodeMeta, healthy: bool) -> u32 {
+		let mut nodes = self.0.lock().unwrap();
+		let Some(instance) = nodes
+			.get_mut(&node.node_name)
+			.and_then(|p| p.instances.iter_mut().find(|i| &i.meta == node))
+		else {
+			return 0;
+		};
+
+		if healthy {
+			instance.failed_checks = 0;
+		} else {
+			instance.failed_checks += 1;
+		}
+		instance.failed_checks
+	}
+
+	/// Removes an instance. Returns `None` if it wasn't known, otherwise whether it was the last
+	/// one of its type.
+	pub fn remove(&self, node: &NodeMeta) -> Option<bool> {
+		let mut nodes = self.0.lock().unwrap();
+		let pool = nodes.get_mut(&node.node_name)?;
+
+		let len = pool.instances.len();
+		pool.instances.retain(|i| &i.meta != node);
+		if pool.instances.len() == len {
+			return None;
+		}
+
+		if pool.instances.is_empty() {
+			nodes.remove(&node.node_name);
+			Some(true)
+		} else {
+			Some(false)
+		}
+	}
+
+	/// Instances that weren't seen for longer than `timeout`.
+	pub fn stale(&self, timeout: Duration) -> Vec<NodeMeta> {
+		self.0
+			.lock()
+			.unwrap()
+			.values()
+			.flat_map(|p| &p.instances)
+			.filter(|i| i.last_seen.elapsed() > timeout)
+			.map(|i| i.meta.clone())
+			.collect()
+	}
+
+	/// Every instance, with the number of requests it has in flight.
+	pub fn instances(&self) -> Vec<(NodeMeta, usize)> {
+		self.0
+			.lock()
+			.unwrap()
+			.values()
+			.flat_map(|p| &p.instances)
+			.map(|i| (i.meta.clone(), i.in_flight.load(Ordering::Relaxed)))
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rssflow_service::proto::node::NodeMeta;
+
+	use super::Nodes;
+
+	fn node(address: &str) -> NodeMeta {
+		NodeMeta {
+			node_name: "Fetch".to_string(),
+			address: address.to_string(),
+			..NodeMeta::default()
+		}
+	}
+
+	#[test]
+	fn remove() {
+		let nodes = Nodes::default();
+		let (a, b) = (node("http://a:50051"), node("http://b:50051"));
+		nodes.insert(a.clone());
+		nodes.insert(b.clone());
+
+		assert_eq!(nodes.remove(&a), Some(false));
+		assert_eq!(nodes.remove(&a), None);
+		assert_eq!(nodes.remove(&b), Some(true));
+		assert!(!nodes.contains("Fetch"));
+		assert_eq!(nodes.remove(&b), None);
+	}
+
+	#[test]
+	fn failed_checks() {
+		let nodes = Nodes::default();
+		let a = node("http://a:50051");
+		nodes.insert(a.clone());
+
+		assert_eq!(nodes.record_check(&a, false), 1);
+		assert_eq!(nodes.record_check(&a, false), 2);
+		assert_eq!(nodes.record_check(&a, true), 0);
+		assert_eq!(nodes.record_check(&a, false), 1);
+		assert_eq!(nodes.record_check(&node("http://b:50051"), false), 0);
+	}
+}
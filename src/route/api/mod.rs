@@ -1,16 +1,24 @@
 use axum::{
 	Extension, Json, Router,
-	extract::{Path, State},
+	extract::{Path, Query, State},
 	http::StatusCode,
-	response::IntoResponse,
-	routing::{delete, get, put},
+	response::{IntoResponse, Response},
+	routing::{delete, get, post, put},
 };
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use tracing::{error, instrument};
 
+use self::revisions::RevisionQuery;
 use super::internal_error;
-use crate::{RSSFlow, flow::Flow};
+use crate::{
+	RSSFlow,
+	flow::{Flow, ValidationError, revision, snapshot},
+};
+
+mod nodes;
+mod revisions;
+mod runs;
 
 #[derive(Serialize, Deserialize)]
 struct FlowResult {
@@ -56,59 +64,73 @@ async fn get_flow(
 	Ok(Json(flow))
 }
 
+#[derive(Serialize)]
+struct ValidationResult {
+	valid: bool,
+	errors: Vec<ValidationError>,
+}
+
+impl RSSFlow {
+	fn validate(&self, flow: &Flow) -> ValidationResult {
+		let descriptions = self.descriptions.lock().unwrap();
+		let errors = flow.validate(&self.nodes, &descriptions);
+
+		ValidationResult {
+			valid: errors.is_empty(),
+			errors,
+		}
+	}
+}
+
+#[instrument(skip_all)]
+async fn validate_flow(State(state): State<RSSFlow>, Json(flow): Json<Flow>) -> impl IntoResponse {
+	Json(state.validate(&flow))
+}
+
 #[instrument(skip_all)]
 async fn update_flow(
 	Path(name): Path<String>,
+	Query(query): Query<RevisionQuery>,
 	State(state): State<RSSFlow>,
 	Extension(pool): Extension<PgPool>,
 	Json(flow): Json<Flow>,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
-	let json = serde_json::to_value(&flow).map_err(internal_error)?;
-
-	// let flow = flow.build();
-	// flow.run()
-	// 	.await
-	// 	.map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
-
-	let mut conn = pool.acquire().await.map_err(internal_error)?;
-	let update = sqlx::query_scalar!("SELECT EXISTS(SELECT 1 FROM flows WHERE name = $1)", name)
-		.fetch_one(&mut *conn)
-		.await
-		.map_err(internal_error)?
-		.unwrap_or_default();
-
-	// if flow.has_subscriptions() {
-	// 	state
-	// 		.web_sub_subscriber
-	// 		.register_flow(&name, &flow)
-	// 		.await
-	// 		.map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
-	// }
+) -> Result<Response, (StatusCode, String)> {
+	let result = state.validate(&flow);
+	if !result.valid {
+		return Ok((StatusCode::UNPROCESSABLE_ENTITY, Json(result)).into_response());
+	}
 
-	// state
-	// 	.flows
-	// 	.lock()
-	// 	.await
-	// 	.insert(name.clone(), FlowHandle::new(Arc::new(flow)));
+	let json = serde_json::to_value(&flow).map_err(internal_error)?;
 
-	if update {
-		sqlx::query!("UPDATE flows SET content = $1 WHERE name = $2", json, name)
-			.execute(&mut *conn)
+	let mut tx = pool.begin().await.map_err(internal_error)?;
+	// xmax is only set on rows that existed before, i.e. were updated.
+	let created = sqlx::query_scalar!(
+		r#"INSERT INTO flows (name, content) VALUES ($1, $2) ON CONFLICT (name) DO UPDATE SET content = EXCLUDED.content RETURNING (xmax = 0) AS "created!""#,
+		name,
+		json
+	)
+	.fetch_one(&mut *tx)
+	.await
+	.map_err(internal_error)?;
+	revision::record(
+		&mut *tx,
+		&name,
+		&json,
+		query.author.as_deref(),
+		query.message.as_deref(),
+	)
+	.await
+	.map_err(internal_error)?;
+	tx.commit().await.map_err(internal_error)?;
+
+	if created {
+		Ok(StatusCode::CREATED.into_response())
+	} else {
+		snapshot::clear(&pool, &name)
 			.await
 			.map_err(internal_error)?;
 
-		Ok(StatusCode::NO_CONTENT)
-	} else {
-		sqlx::query!(
-			"INSERT INTO flows (name, content) VALUES ($1, $2)",
-			name,
-			json
-		)
-		.execute(&mut *conn)
-		.await
-		.map_err(internal_error)?;
-
-		Ok(StatusCode::CREATED)
+		Ok(StatusCode::NO_CONTENT.into_response())
 	}
 }
 
@@ -136,7 +158,18 @@ pub fn router() -> Router<RSSFlow> {
 	Router::new()
 		// .route("/flow", post(create_flow))
 		.route("/flow", get(get_flows))
+		.route("/flow/validate", post(validate_flow))
 		.route("/flow/{name}", get(get_flow))
 		.route("/flow/{name}", put(update_flow))
 		.route("/flow/{name}", delete(delete_flow))
+		.route("/flow/{name}/revisions", get(revisions::get_revisions))
+		.route("/flow/{name}/revisions/{n}", get(revisions::get_revision))
+		.route(
+			"/flow/{name}/revisions/{n}/diff",
+			get(revisions::diff_revision),
+		)
+		.route("/flow/{name}/rollback/{n}", post(revisions::rollback))
+		.route("/flow/{name}/runs", get(runs::get_runs))
+		.route("/runs/{id}", get(runs::get_run))
+		.route("/nodes", get(nodes::get_nodes))
 }
@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+
+use axum::{Json, extract::State, response::IntoResponse};
+use prost_types::value::Kind;
+use rssflow_service::proto::node::{NodeDescription, OptionSchema};
+use serde::Serialize;
+use tracing::instrument;
+
+use crate::RSSFlow;
+
+#[derive(Serialize)]
+struct OptionInfo {
+	name: String,
+	r#type: String,
+	required: bool,
+	#[serde(skip_serializing_if = "String::is_empty")]
+	description: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	default: Option<serde_json::Value>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	values: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct InstanceInfo {
+	address: String,
+	in_flight: usize,
+}
+
+/// A known node type, with its description if it provides one.
+#[derive(Serialize)]
+struct NodeInfo {
+	name: String,
+	instances: Vec<InstanceInfo>,
+	#[serde(flatten)]
+	description: Option<DescriptionInfo>,
+}
+
+#[derive(Serialize)]
+struct DescriptionInfo {
+	description: String,
+	input_types: Vec<String>,
+	multiple_inputs: bool,
+	output_type: String,
+	options: Vec<OptionInfo>,
+}
+
+fn to_json(value: &prost_types::Value) -> serde_json::Value {
+	match &value.kind {
+		None | Some(Kind::NullValue(_)) => serde_json::Value::Null,
+		Some(Kind::NumberValue(n)) => serde_json::Number::from_f64(*n)
+			.map_or(serde_json::Value::Null, serde_json::Value::Number),
+		Some(Kind::StringValue(s)) => s.clone().into(),
+		Some(Kind::BoolValue(b)) => (*b).into(),
+		Some(Kind::StructValue(s)) => s
+			.fields
+			.iter()
+			.map(|(k, v)| (k.clone(), to_json(v)))
+			.collect::<serde_json::Map<_, _>>()
+			.into(),
+		Some(Kind::ListValue(l)) => l.values.iter().map(to_json).collect(),
+	}
+}
+
+impl From<&OptionSchema> for OptionInfo {
+	fn from(option: &OptionSchema) -> Self {
+		OptionInfo {
+			name: option.name.clone(),
+			r#type: option.r#type().as_str_name().to_lowercase(),
+			required: option.required,
+			description: option.description.clone(),
+			default: option.default.as_ref().map(to_json),
+			values: option.values.clone(),
+		}
+	}
+}
+
+impl From<&NodeDescription> for DescriptionInfo {
+	fn from(description: &NodeDescription) -> Self {
+		DescriptionInfo {
+			description: description.description.clone(),
+			input_types: description.input_types.clone(),
+			multiple_inputs: description.multiple_inputs,
+			output_type: description.output_type.clone(),
+			options: description.options.iter().map(Into::into).collect(),
+		}
+	}
+}
+
+#[instrument(skip_all)]
+pub async fn get_nodes(State(state): State<RSSFlow>) -> impl IntoResponse {
+	let mut instances: BTreeMap<String, Vec<InstanceInfo>> = BTreeMap::new();
+	for (node, in_flight) in state.nodes.instances() {
+		instances
+			.entry(node.node_name)
+			.or_default()
+			.push(InstanceInfo {
+				address: node.address,
+				in_flight,
+			});
+	}
+
+	let descriptions = state.descriptions.lock().unwrap();
+	let catalog: Vec<NodeInfo> = instances
+		.into_iter()
+		.map(|(name, instances)| NodeInfo {
+			description: descriptions.get(&name).map(Into::into),
+			name,
+			instances,
+		})
+		.collect();
+
+	Json(catalog)
+}
@@ -0,0 +1,284 @@
+use axum::{
+	Extension, Json,
+	extract::{Path, Query, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use tracing::instrument;
+
+use super::internal_error;
+use crate::{
+	RSSFlow,
+	flow::{
+		Flow,
+		revision::{self, Revision},
+		snapshot,
+	},
+};
+
+/// Describes the revision a change creates.
+#[derive(Deserialize)]
+pub struct RevisionQuery {
+	pub author: Option<String>,
+	pub message: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RevisionResult {
+	#[serde(flatten)]
+	revision: Revision,
+	content: Value,
+}
+
+fn not_found() -> (StatusCode, String) {
+	(StatusCode::NOT_FOUND, String::from("Not found"))
+}
+
+#[instrument(skip_all)]
+pub async fn get_revisions(
+	Path(name): Path<String>,
+	Extension(pool): Extension<PgPool>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let revisions = revision::list(&pool, &name).await.map_err(internal_error)?;
+	// Every stored flow has at least one revision.
+	if revisions.is_empty() {
+		return Err(not_found());
+	}
+
+	Ok(Json(revisions))
+}
+
+#[instrument(skip_all)]
+pub async fn get_revision(
+	Path((name, n)): Path<(String, i32)>,
+	Extension(pool): Extension<PgPool>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let (revision, content) = revision::get(&pool, &name, n)
+		.await
+		.map_err(internal_error)?
+		.ok_or_else(not_found)?;
+
+	Ok(Json(RevisionResult { revision, content }))
+}
+
+#[derive(Deserialize)]
+struct DiffQuery {
+	/// Revision to compare against, the previous one by default.
+	from: Option<i32>,
+}
+
+/// A JSON Patch (RFC 6902) turning revision `from` into revision `n`.
+#[instrument(skip_all)]
+pub async fn diff_revision(
+	Path((name, n)): Path<(String, i32)>,
+	Query(query): Query<DiffQuery>,
+	Extension(pool): Extension<PgPool>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let (_, content) = revision::get(&pool, &name, n)
+		.await
+		.map_err(internal_error)?
+		.ok_or_else(not_found)?;
+
+	let from = query.from.or(Some(n - 1).filter(|from| *from > 0));
+	// The first revision is diffed against nothing.
+	let base = match from {
+		Some(from) => {
+			revision::get(&pool, &name, from)
+				.await
+				.map_err(internal_error)?
+				.ok_or_else(not_found)?
+				.1
+		}
+		None => Value::Null,
+	};
+
+	Ok(Json(json_patch::diff(&base, &content)))
+}
+
+#[derive(Serialize)]
+struct RollbackResult {
+	/// The revision the rollback created.
+	revision: i32,
+}
+
+/// Restores revision `n` of the flow, as a new revision.
+#[instrument(skip_all)]
+pub async fn rollback(
+	Path((name, n)): Path<(String, i32)>,
+	Query(query): Query<RevisionQuery>,
+	State(state): State<RSSFlow>,
+	Extension(pool): Extension<PgPool>,
+) -> Result<Response, (StatusCode, String)> {
+	let (_, content) = revision::get(&pool, &name, n)
+		.await
+		.map_err(internal_error)?
+		.ok_or_else(not_found)?;
+
+	// The flow format may have changed since, and nodes the revision used may be gone by now.
+	let flow: Flow = serde_json::from_value(content.clone())
+		.map_err(|err| (StatusCode::UNPROCESSABLE_ENTITY, err.to_string()))?;
+	let result = state.validate(&flow);
+	if !result.valid {
+		return Ok((StatusCode::UNPROCESSABLE_ENTITY, Json(result)).into_response());
+	}
+
+	let message = query
+		.message
+		.unwrap_or_else(|| format!("Rollback to revision {n}"));
+
+	let mut tx = pool.begin().await.map_err(internal_error)?;
+	let updated = sqlx::query!(
+		"UPDATE flows SET content = $1 WHERE name = $2",
+		content,
+		name
+	)
+	.execute(&mut *tx)
+	.await
+	.map_err(internal_error)?;
+	// Revisions outlive their flow, which can't be rolled back once deleted.
+	if updated.rows_affected() == 0 {
+		return Err(not_found());
+	}
+	let revision = revision::record(
+		&mut *tx,
+		&name,
+		&content,
+		query.author.as_deref(),
+		Some(&message),
+	)
+	.await
+	.map_err(internal_error)?;
+	tx.commit().await.map_err(internal_error)?;
+
+	snapshot::clear(&pool, &name)
+		.await
+		.map_err(internal_error)?;
+
+	Ok(Json(RollbackResult { revision }).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+
+	use axum::{
+		Extension, Json,
+		body::to_bytes,
+		extract::{Path, Query, State},
+		http::StatusCode,
+		response::{IntoResponse, Response},
+	};
+	use serde_json::{Value, json};
+	use sqlx::PgPool;
+
+	use super::{DiffQuery, RevisionQuery, diff_revision, rollback};
+	use crate::{RSSFlow, flow::revision, route::api::update_flow};
+
+	fn query() -> Query<RevisionQuery> {
+		Query(RevisionQuery {
+			author: None,
+			message: None,
+		})
+	}
+
+	async fn save(state: &RSSFlow, pool: &PgPool, content: Value) {
+		update_flow(
+			Path("news".to_string()),
+			query(),
+			State(state.clone()),
+			Extension(pool.clone()),
+			Json(serde_json::from_value(content).unwrap()),
+		)
+		.await
+		.unwrap();
+	}
+
+	async fn json(response: Response) -> Value {
+		let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+		serde_json::from_slice(&body).unwrap()
+	}
+
+	#[sqlx::test]
+	async fn diff_against_previous(pool: PgPool) {
+		let state = RSSFlow(Arc::default());
+		save(&state, &pool, json!({ "nodes": [] })).await;
+		save(&state, &pool, json!({ "nodes": [], "interval": 60 })).await;
+
+		let diff = |n: i32, from: Option<i32>| {
+			diff_revision(
+				Path(("news".to_string(), n)),
+				Query(DiffQuery { from }),
+				Extension(pool.clone()),
+			)
+		};
+
+		let patch = json(diff(2, None).await.unwrap().into_response()).await;
+		assert_eq!(
+			patch,
+			json!([{ "op": "add", "path": "/interval", "value": 60 }])
+		);
+		let patch = json(diff(1, Some(2)).await.unwrap().into_response()).await;
+		assert_eq!(patch, json!([{ "op": "remove", "path": "/interval" }]));
+		// The first revision has nothing before it.
+		let patch = json(diff(1, None).await.unwrap().into_response()).await;
+		let patch: json_patch::Patch = serde_json::from_value(patch).unwrap();
+		let mut content = Value::Null;
+		json_patch::patch(&mut content, &patch.0).unwrap();
+		assert_eq!(content, json!({ "nodes": [] }));
+	}
+
+	#[sqlx::test]
+	async fn rollback_creates_revision(pool: PgPool) {
+		let state = RSSFlow(Arc::default());
+		save(&state, &pool, json!({ "nodes": [], "interval": 60 })).await;
+		save(&state, &pool, json!({ "nodes": [] })).await;
+
+		let response = rollback(
+			Path(("news".to_string(), 1)),
+			query(),
+			State(state.clone()),
+			Extension(pool.clone()),
+		)
+		.await
+		.unwrap();
+		assert_eq!(json(response).await, json!({ "revision": 3 }));
+
+		let content = sqlx::query_scalar!("SELECT content FROM flows WHERE name = $1", "news")
+			.fetch_one(&pool)
+			.await
+			.unwrap();
+		assert_eq!(content, json!({ "nodes": [], "interval": 60 }));
+
+		let revisions = revision::list(&pool, "news").await.unwrap();
+		assert_eq!(revisions.len(), 3);
+		assert_eq!(
+			revisions[0].message.as_deref(),
+			Some("Rollback to revision 1")
+		);
+	}
+
+	#[sqlx::test]
+	async fn rollback_outdated_revision(pool: PgPool) {
+		let state = RSSFlow(Arc::default());
+		save(&state, &pool, json!({ "nodes": [] })).await;
+		let mut conn = pool.acquire().await.unwrap();
+		revision::record(&mut conn, "news", &json!({ "nodes": 1 }), None, None)
+			.await
+			.unwrap();
+		drop(conn);
+
+		let (status, message) = rollback(
+			Path(("news".to_string(), 2)),
+			query(),
+			State(state),
+			Extension(pool.clone()),
+		)
+		.await
+		.unwrap_err();
+		assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+		assert!(message.contains("invalid type"), "{message}");
+	}
+}
@@ -0,0 +1,45 @@
+use axum::{Extension, Json, extract::Path, http::StatusCode, response::IntoResponse};
+use serde::Serialize;
+use sqlx::PgPool;
+use tracing::instrument;
+
+use super::internal_error;
+use crate::flow::run::{self, NodeTrace, Run};
+
+#[derive(Serialize)]
+struct RunResult {
+	#[serde(flatten)]
+	run: Run,
+	nodes: Vec<NodeTrace>,
+}
+
+#[instrument(skip_all)]
+pub async fn get_runs(
+	Path(name): Path<String>,
+	Extension(pool): Extension<PgPool>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let exists = sqlx::query_scalar!("SELECT EXISTS(SELECT 1 FROM flows WHERE name = $1)", name)
+		.fetch_one(&pool)
+		.await
+		.map_err(internal_error)?
+		.unwrap_or_default();
+	if !exists {
+		return Err((StatusCode::NOT_FOUND, String::from("Not found")));
+	}
+
+	let runs = run::list(&pool, &name).await.map_err(internal_error)?;
+	Ok(Json(runs))
+}
+
+#[instrument(skip_all)]
+pub async fn get_run(
+	Path(id): Path<i64>,
+	Extension(pool): Extension<PgPool>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let (run, nodes) = run::get(&pool, id)
+		.await
+		.map_err(internal_error)?
+		.ok_or((StatusCode::NOT_FOUND, String::from("Not found")))?;
+
+	Ok(Json(RunResult { run, nodes }))
+}
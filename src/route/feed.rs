@@ -0,0 +1,167 @@
+use axum::{
+	Json,
+	http::{HeaderMap, HeaderValue, header},
+	response::{IntoResponse, Response},
+};
+use rssflow_service::proto::feed::{Feed, json::JsonFeed};
+use serde::Deserialize;
+
+static APPLICATION_ATOM_XML: HeaderValue = HeaderValue::from_static("application/atom+xml");
+static APPLICATION_RSS_XML: HeaderValue = HeaderValue::from_static("application/rss+xml");
+static APPLICATION_FEED_JSON: HeaderValue = HeaderValue::from_static("application/feed+json");
+
+pub struct Atom(pub atom_syndication::Feed);
+impl IntoResponse for Atom {
+	fn into_response(self) -> Response {
+		(
+			[(header::CONTENT_TYPE, &APPLICATION_ATOM_XML)],
+			self.0.to_string(),
+		)
+			.into_response()
+	}
+}
+
+pub struct Rss(pub rss::Channel);
+impl IntoResponse for Rss {
+	fn into_response(self) -> Response {
+		(
+			[(header::CONTENT_TYPE, &APPLICATION_RSS_XML)],
+			self.0.to_string(),
+		)
+			.into_response()
+	}
+}
+
+pub struct JsonFeedResponse(pub JsonFeed);
+impl IntoResponse for JsonFeedResponse {
+	fn into_response(self) -> Response {
+		(
+			[(header::CONTENT_TYPE, &APPLICATION_FEED_JSON)],
+			Json(self.0),
+		)
+			.into_response()
+	}
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+	#[default]
+	Atom,
+	Rss,
+	Json,
+}
+
+impl Format {
+	pub const ALL: [Format; 3] = [Format::Atom, Format::Rss, Format::Json];
+
+	/// Extension that selects the format in a flow URL, none for the default one.
+	pub fn extension(self) -> Option<&'static str> {
+		match self {
+			Format::Atom => None,
+			Format::Rss => Some("rss"),
+			Format::Json => Some("json"),
+		}
+	}
+
+	fn from_extension(ext: &str) -> Option<Self> {
+		match ext {
+			"atom" => Some(Format::Atom),
+			"rss" | "xml" => Some(Format::Rss),
+			"json" => Some(Format::Json),
+			_ => None,
+		}
+	}
+
+	fn from_media_type(media_type: &str) -> Option<Self> {
+		match media_type {
+			"application/atom+xml" => Some(Format::Atom),
+			"application/rss+xml" => Some(Format::Rss),
+			"application/feed+json" | "application/json" => Some(Format::Json),
+			"*/*" => Some(Format::default()),
+			_ => None,
+		}
+	}
+
+	/// Splits a known format extension off a path segment, e.g. `name.json`.
+	///
+	/// Callers should check for a flow with the unsplit name first.
+	pub fn split_extension(name: &str) -> (&str, Option<Self>) {
+		name.rsplit_once('.')
+			.and_then(|(stem, ext)| Some((stem, Self::from_extension(ext)?)))
+			.map_or((name, None), |(stem, format)| (stem, Some(format)))
+	}
+
+	/// Picks the supported format with the highest quality value in the `Accept` header.
+	pub fn from_accept(headers: &HeaderMap) -> Option<Self> {
+		headers
+			.get_all(header::ACCEPT)
+			.iter()
+			.filter_map(|v| v.to_str().ok())
+			.flat_map(|v| v.split(','))
+			.filter_map(|range| {
+				let mut params = range.split(';');
+				let format = Self::from_media_type(params.next()?.trim())?;
+				let q = params
+					.filter_map(|p| p.trim().strip_prefix("q="))
+					.find_map(|q| q.parse::<f32>().ok())
+					.unwrap_or(1.0);
+				Some((format, q))
+			})
+			.fold(None, |best, (format, q)| match best {
+				Some((_, best_q)) if best_q >= q => best,
+				_ if q > 0.0 => Some((format, q)),
+				_ => best,
+			})
+			.map(|(format, _)| format)
+	}
+
+	pub fn respond(self, feed: Feed) -> Response {
+		match self {
+			Format::Atom => Atom(feed.into()).into_response(),
+			Format::Rss => Rss(feed.into()).into_response(),
+			Format::Json => JsonFeedResponse(feed.into()).into_response(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use axum::http::{HeaderMap, HeaderValue, header};
+
+	use super::Format;
+
+	fn accept(value: &'static str) -> Option<Format> {
+		let mut headers = HeaderMap::new();
+		headers.insert(header::ACCEPT, HeaderValue::from_static(value));
+		Format::from_accept(&headers)
+	}
+
+	#[test]
+	fn split_extension() {
+		assert_eq!(
+			Format::split_extension("news.json"),
+			("news", Some(Format::Json))
+		);
+		assert_eq!(
+			Format::split_extension("news.xml"),
+			("news", Some(Format::Rss))
+		);
+		assert_eq!(Format::split_extension("news.v2"), ("news.v2", None));
+		assert_eq!(Format::split_extension("news"), ("news", None));
+	}
+
+	#[test]
+	fn from_accept() {
+		assert_eq!(accept("application/rss+xml"), Some(Format::Rss));
+		assert_eq!(
+			accept("application/atom+xml;q=0.5, application/feed+json"),
+			Some(Format::Json)
+		);
+		assert_eq!(accept("*/*"), Some(Format::Atom));
+		assert_eq!(accept("text/html, */*;q=0.8"), Some(Format::Atom));
+		assert_eq!(accept("application/rss+xml, */*;q=0.8"), Some(Format::Rss));
+		assert_eq!(accept("text/html"), None);
+		assert_eq!(accept("application/rss+xml;q=0"), None);
+	}
+}
@@ -1,101 +1,209 @@
-use std::collections::HashMap;
+use std::sync::Arc;
 
 use axum::{
 	Extension, Router,
-	extract::{Path, State},
-	http::StatusCode,
-	response::IntoResponse,
+	extract::{
+		Path, Query, State,
+		ws::{Message, WebSocketUpgrade},
+	},
+	http::{HeaderMap, StatusCode},
+	response::{
+		IntoResponse,
+		sse::{Event, KeepAlive, Sse},
+	},
 	routing::get,
 };
-use rssflow_service::{
-	NodeExt,
-	proto::node::{NodeMeta, ProcessRequest},
-};
-use sqlx::PgPool;
-use tracing::{info, instrument};
+use futures::{Stream, StreamExt};
+use serde::Deserialize;
+use sqlx::{PgConnection, PgPool};
+use tracing::instrument;
 
 use crate::{
 	RSSFlow,
-	flow::Flow,
-	route::{Atom, internal_error},
+	flow::{Flow, run::Trigger, snapshot},
+	hub,
+	route::{feed::Format, internal_error},
+	stream::FlowEvent,
 };
 
+#[derive(Deserialize)]
+struct RunQuery {
+	/// Id of the output node to serve, required if the flow has more than one.
+	output: Option<String>,
+	format: Option<Format>,
+	/// Run the flow now instead of serving its latest snapshot.
+	#[serde(default)]
+	refresh: bool,
+}
+
+async fn load(
+	conn: &mut PgConnection,
+	name: &str,
+) -> Result<Option<serde_json::Value>, (StatusCode, String)> {
+	sqlx::query_scalar!("SELECT content FROM flows WHERE name = $1", name)
+		.fetch_optional(conn)
+		.await
+		.map_err(internal_error)
+}
+
 #[instrument(skip_all)]
 async fn run(
 	Path(name): Path<String>,
+	Query(query): Query<RunQuery>,
 	State(state): State<RSSFlow>,
 	Extension(pool): Extension<PgPool>,
+	headers: HeaderMap,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
 	let mut conn = pool.acquire().await.map_err(internal_error)?;
-	let content = sqlx::query_scalar!("SELECT content FROM flows WHERE name = $1", name)
-		.fetch_one(&mut *conn)
-		.await
-		.map_err(|_| (StatusCode::NOT_FOUND, String::from("Not found")))?;
+	// Flow names may contain dots, so the suffix is only a format if no flow has the full name.
+	let (name, extension, content) = match load(&mut conn, &name).await? {
+		Some(content) => (name.as_str(), None, content),
+		None => {
+			let (stem, extension) = Format::split_extension(&name);
+			let content = match extension {
+				Some(_) => load(&mut conn, stem).await?,
+				None => None,
+			}
+			.ok_or((StatusCode::NOT_FOUND, String::from("Not found")))?;
+			(stem, extension, content)
+		}
+	};
+	let format = query
+		.format
+		.or(extension)
+		.or_else(|| Format::from_accept(&headers))
+		.unwrap_or_default();
 
 	let flow: Flow = serde_json::from_value(content).map_err(internal_error)?;
 
-	let known_nodes: HashMap<String, NodeMeta> = state.nodes.lock().unwrap().clone();
-
-	let mut payload = None;
-
-	for node in flow.nodes {
-		let service = known_nodes.get(&node.r#type).ok_or((
-			StatusCode::UNPROCESSABLE_ENTITY,
-			format!("No such node: {}", node.r#type),
-		))?;
-
-		info!("Sending request to {} node", node.r#type);
-		let res = service
-			.process(ProcessRequest {
-				payload,
-				options: node.options(),
-			})
+	// Snapshots of unscheduled flows would never be refreshed.
+	let snapshot = if flow.interval.is_some() && !query.refresh {
+		snapshot::load(&pool, name).await.map_err(internal_error)?
+	} else {
+		None
+	};
+	let mut outputs = match snapshot {
+		Some(outputs) => outputs,
+		None => state
+			.refresh(&pool, name, &flow, Trigger::Request)
 			.await
-			.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-		payload = res.into_inner().payload;
-	}
+			.map_err(|e| (e.status_code(), e.to_string()))?,
+	};
+
+	let payload = if let Some(output) = query.output {
+		outputs
+			.remove(&output)
+			.ok_or((StatusCode::NOT_FOUND, format!("No such output: {output}")))?
+	} else if outputs.len() <= 1 {
+		outputs.into_values().next().flatten()
+	} else {
+		return Err((
+			StatusCode::BAD_REQUEST,
+			format!(
+				"Flow has multiple outputs, select one with ?output=: {}",
+				outputs.into_keys().collect::<Vec<_>>().join(", ")
+			),
+		));
+	};
 
 	if let Some(payload) = payload {
-		let feed: rssflow_service::proto::feed::Feed =
+		let mut feed: rssflow_service::proto::feed::Feed =
 			rssflow_service::proto::feed::Feed::try_from(payload).map_err(internal_error)?;
-		Ok(Atom(feed.into()).into_response())
+		hub::advertise(&mut feed, name, format);
+		Ok(format.respond(feed))
 	} else {
 		Ok(().into_response())
 	}
 }
 
+#[derive(Deserialize)]
+struct StreamQuery {
+	/// Alternative to the `Last-Event-ID` header, which browsers can't set on WebSockets.
+	last_event_id: Option<u64>,
+}
+
+/// Subscribes to the new entries of the flow `name`, resuming after the last event the client
+/// got.
+async fn events(
+	name: &str,
+	state: &RSSFlow,
+	pool: &PgPool,
+	query: StreamQuery,
+	headers: &HeaderMap,
+) -> Result<impl Stream<Item = Arc<FlowEvent>> + use<>, (StatusCode, String)> {
+	let exists = sqlx::query_scalar!("SELECT EXISTS(SELECT 1 FROM flows WHERE name = $1)", name)
+		.fetch_one(pool)
+		.await
+		.map_err(internal_error)?
+		.unwrap_or_default();
+	if !exists {
+		return Err((StatusCode::NOT_FOUND, String::from("Not found")));
+	}
+
+	let last_event_id = query.last_event_id.or_else(|| {
+		headers
+			.get("last-event-id")
+			.and_then(|v| v.to_str().ok())
+			.and_then(|v| v.parse().ok())
+	});
+	Ok(state.streams.subscribe(name, last_event_id))
+}
+
+#[instrument(skip_all)]
+async fn sse(
+	Path(name): Path<String>,
+	Query(query): Query<StreamQuery>,
+	State(state): State<RSSFlow>,
+	Extension(pool): Extension<PgPool>,
+	headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, (StatusCode, String)> {
+	let stream = events(&name, &state, &pool, query, &headers)
+		.await?
+		.map(|event| {
+			Event::default()
+				.id(event.id.to_string())
+				.event("entry")
+				.json_data(&*event)
+		});
+
+	Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
 #[instrument(skip_all)]
-async fn subscribe(Path(name): Path<String>, State(state): State<RSSFlow>) -> StatusCode
-//-> Result<Sse<impl Stream<Item = anyhow::Result<Event>>>, StatusCode>
-{
-	// let Some((_flow, rx)) = state
-	// 	.flows
-	// 	.lock()
-	// 	.await
-	// 	.get(&name)
-	// 	.map(|h| ((*h).clone(), h.subscribe()))
-	// else {
-	// 	return Err(StatusCode::NOT_FOUND);
-	// };
-
-	// let stream = BroadcastStream::new(rx).map(|res| {
-	// 	// let entries = res.map(|d| {
-	// 	// 	if let Data::Feed(feed) = d {
-	// 	// 		Data::Vec(feed.entries.into_iter().map(Data::Entry).collect())
-	// 	// 	} else {
-	// 	// 		d
-	// 	// 	}
-	// 	// });
-	//
-	// 	Ok(Event::default().json_data(res?)?)
-	// });
-	// Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
-
-	StatusCode::NOT_FOUND
+async fn websocket(
+	Path(name): Path<String>,
+	Query(query): Query<StreamQuery>,
+	State(state): State<RSSFlow>,
+	Extension(pool): Extension<PgPool>,
+	headers: HeaderMap,
+	upgrade: WebSocketUpgrade,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+	let mut stream = Box::pin(events(&name, &state, &pool, query, &headers).await?);
+
+	Ok(upgrade.on_upgrade(move |mut socket| async move {
+		loop {
+			tokio::select! {
+				event = stream.next() => {
+					let Some(event) = event else { break };
+					let Ok(json) = serde_json::to_string(&*event) else { continue };
+					if socket.send(Message::Text(json.into())).await.is_err() {
+						break;
+					}
+				}
+				message = socket.recv() => {
+					// Incoming messages are ignored, the socket only streams events.
+					if !matches!(message, Some(Ok(_))) {
+						break;
+					}
+				}
+			}
+		}
+	}))
 }
 
 pub fn router() -> Router<RSSFlow> {
 	Router::new()
 		.route("/{name}", get(run))
-		.route("/{name}/sse", get(subscribe))
+		.route("/{name}/sse", get(sse))
+		.route("/{name}/ws", get(websocket))
 }
@@ -1,9 +1,7 @@
-use axum::{
-	http::{HeaderValue, StatusCode, header},
-	response::{IntoResponse, Response},
-};
+use axum::http::StatusCode;
 
 mod api;
+pub mod feed;
 mod flow;
 
 pub use api::router as api;
@@ -15,16 +13,3 @@ where
 {
 	(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
 }
-
-static APPLICATION_ATOM_XML: HeaderValue = HeaderValue::from_static("application/atom+xml");
-
-struct Atom(atom_syndication::Feed);
-impl IntoResponse for Atom {
-	fn into_response(self) -> Response {
-		(
-			[(header::CONTENT_TYPE, &APPLICATION_ATOM_XML)],
-			self.0.to_string(),
-		)
-			.into_response()
-	}
-}
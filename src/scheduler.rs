@@ -0,0 +1,250 @@
+use std::{
+	collections::HashMap,
+	fmt,
+	sync::{
+		Arc, Mutex,
+		atomic::{AtomicU64, Ordering},
+	},
+	time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use chrono::Utc;
+use futures::future::{BoxFuture, FutureExt, Shared};
+use sqlx::PgPool;
+use tokio::{sync::OwnedMutexGuard, task::JoinHandle};
+use tracing::{error, info, warn};
+
+use crate::{
+	RSSFlow,
+	flow::{
+		self, Flow, Outputs,
+		run::{self, Trigger},
+	},
+	hub,
+};
+
+/// How often the scheduler looks for flows that are due.
+pub const TICK: Duration = Duration::from_secs(5);
+
+/// Per-flow locks, so runs of the same flow publish and snapshot their outputs in order.
+#[derive(Debug, Default)]
+pub struct FlowLocks(Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>);
+
+impl FlowLocks {
+	/// Waits for other refreshes of the flow `name` to finish.
+	pub async fn lock(&self, name: &str) -> OwnedMutexGuard<()> {
+		let lock = self
+			.0
+			.lock()
+			.unwrap()
+			.entry(name.to_string())
+			.or_default()
+			.clone();
+		lock.lock_owned().await
+	}
+}
+
+type SharedRun = Shared<BoxFuture<'static, Result<Outputs, Arc<flow::Error>>>>;
+
+/// The latest run started per flow while it's going, so requests can wait for its outputs instead
+/// of running the flow again.
+#[derive(Default)]
+pub struct InFlight(Mutex<HashMap<String, (u64, SharedRun)>>);
+
+impl InFlight {
+	/// Forgets run `id` of the flow `name`, unless a newer run replaced it already.
+	fn finish(&self, name: &str, id: u64) {
+		let mut runs = self.0.lock().unwrap();
+		if runs.get(name).is_some_and(|(current, _)| *current == id) {
+			runs.remove(name);
+		}
+	}
+}
+
+impl fmt::Debug for InFlight {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_set()
+			.entries(self.0.lock().unwrap().keys())
+			.finish()
+	}
+}
+
+/// When each scheduled flow's last run started, and the runs still going.
+#[derive(Default)]
+pub struct Schedule {
+	last_run: HashMap<String, Instant>,
+	running: HashMap<String, JoinHandle<()>>,
+}
+
+impl RSSFlow {
+	/// Runs the flow, publishes the entries that are new since the latest snapshot and, if the
+	/// flow is scheduled, stores its outputs as the latest snapshot.
+	///
+	/// Requests share the outputs of a run of the flow that's already going. Other triggers start
+	/// a new run, since whatever triggered them may have changed the flow's sources, and runs of
+	/// the same flow happen one at a time.
+	pub async fn refresh(
+		&self,
+		pool: &PgPool,
+		name: &str,
+		flow: &Flow,
+		trigger: Trigger,
+	) -> Result<Outputs, Arc<flow::Error>> {
+		static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+		let run = {
+			let mut runs = self.in_flight.0.lock().unwrap();
+			let running = runs
+				.get(name)
+				.filter(|_| matches!(trigger, Trigger::Request))
+				.map(|(_, run)| run.clone());
+			match running {
+				Some(run) => run,
+				None => {
+					let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+					let run = {
+						let svc = self.clone();
+						let pool = pool.clone();
+						let name = name.to_string();
+						let flow = flow.clone();
+						async move {
+							let result = svc.run(&pool, &name, &flow, trigger).await;
+							svc.in_flight.finish(&name, id);
+							result.map_err(Arc::new)
+						}
+						.boxed()
+						.shared()
+					};
+					runs.insert(name.to_string(), (id, run.clone()));
+					run
+				}
+			}
+		};
+		run.await
+	}
+
+	/// Runs the flow once the previous run finished, recording the run in the flow's history.
+	async fn run(
+		&self,
+		pool: &PgPool,
+		name: &str,
+		flow: &Flow,
+		trigger: Trigger,
+	) -> Result<Outputs, flow::Error> {
+		let _guard = self.flow_locks.lock(name).await;
+
+		let started_at = Utc::now();
+		let mut traces = Vec::new();
+		let result = flow.run(name, &self.nodes, &mut traces).await;
+
+		let error = result.as_ref().err().map(ToString::to_string);
+		if let Err(err) =
+			run::save(pool, name, trigger, started_at, error.as_deref(), &traces).await
+		{
+			error!("Recording run of `{name}` failed: {err}");
+		}
+		let outputs = result?;
+
+		// Only scheduled flows are served from snapshots, others would never be refreshed.
+		let scheduled = flow.interval.is_some();
+		let previous = if scheduled {
+			flow::snapshot::load(pool, name)
+				.await
+				.inspect_err(|err| error!("Loading snapshot of `{name}` failed: {err}"))
+				.ok()
+				.flatten()
+		} else {
+			None
+		};
+		let published = self.streams.publish(name, previous.as_ref(), &outputs);
+
+		if scheduled {
+			let saved = flow::snapshot::save(pool, name, &outputs).await;
+			if let Err(err) = saved {
+				error!("Saving snapshot of `{name}` failed: {err}");
+			}
+		}
+
+		// The hub fetches the feed when notified, so only after the snapshot is saved.
+		if published > 0 {
+			hub::notify(name).await;
+		}
+		Ok(outputs)
+	}
+
+	/// Loads the flow `name` and refreshes it.
+	pub async fn refresh_stored(
+		&self,
+		pool: &PgPool,
+		name: &str,
+		trigger: Trigger,
+	) -> anyhow::Result<()> {
+		let content = sqlx::query_scalar!("SELECT content FROM flows WHERE name = $1", name)
+			.fetch_optional(pool)
+			.await?
+			.ok_or_else(|| anyhow!("No such flow: {name}"))?;
+		let flow: Flow = serde_json::from_value(content)?;
+
+		self.refresh(pool, name, &flow, trigger).await?;
+		Ok(())
+	}
+
+	/// Starts a run of every flow whose interval elapsed since its last run started, unless that
+	/// run is still going.
+	pub async fn run_scheduled(&self, pool: &PgPool, schedule: &mut Schedule) {
+		let records = match sqlx::query!("SELECT name, content FROM flows")
+			.fetch_all(pool)
+			.await
+		{
+			Ok(records) => records,
+			Err(err) => {
+				error!("Loading flows failed: {err}");
+				return;
+			}
+		};
+
+		let scheduled: Vec<(String, Flow, Duration)> = records
+			.into_iter()
+			.filter_map(|r| {
+				let flow: Flow = serde_json::from_value(r.content).ok()?;
+				let interval = Duration::from_secs(flow.interval.filter(|&i| i > 0)?);
+				Some((r.name, flow, interval))
+			})
+			.collect();
+
+		// Flows that were removed or unscheduled don't need tracking anymore.
+		schedule
+			.last_run
+			.retain(|name, _| scheduled.iter().any(|(n, ..)| n == name));
+		schedule.running.retain(|_, run| !run.is_finished());
+
+		for (name, flow, interval) in scheduled {
+			let due = schedule
+				.last_run
+				.get(&name)
+				.is_none_or(|run| run.elapsed() >= interval);
+			if !due {
+				continue;
+			}
+			if schedule.running.contains_key(&name) {
+				warn!("Previous run of `{name}` is still going, skipping");
+				continue;
+			}
+
+			schedule.last_run.insert(name.clone(), Instant::now());
+			let svc = self.clone();
+			let pool = pool.clone();
+			let run = tokio::spawn({
+				let name = name.clone();
+				async move {
+					match svc.refresh(&pool, &name, &flow, Trigger::Schedule).await {
+						Ok(_) => info!("Refreshed `{name}`"),
+						Err(err) => error!("Scheduled run of `{name}` failed: {err}"),
+					}
+				}
+			});
+			schedule.running.insert(name, run);
+		}
+	}
+}
@@ -0,0 +1,215 @@
+use std::{
+	collections::{HashMap, HashSet, VecDeque},
+	sync::{Arc, Mutex},
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures::{Stream, StreamExt, stream};
+use rssflow_service::proto::feed::{Entry, Feed, json::Item};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::flow::Outputs;
+
+/// Events kept per flow for clients resuming with `Last-Event-ID`.
+const HISTORY: usize = 100;
+
+/// An entry a flow run produced that the previous run didn't.
+#[derive(Serialize, Debug)]
+pub struct FlowEvent {
+	pub id: u64,
+	/// Id of the output node that produced the entry.
+	pub output: String,
+	pub entry: Item,
+}
+
+#[derive(Debug)]
+struct Channel {
+	sender: broadcast::Sender<Arc<FlowEvent>>,
+	recent: VecDeque<Arc<FlowEvent>>,
+	next_id: u64,
+	/// Entry ids of the latest run's outputs, by output node id.
+	latest: Option<HashMap<String, HashSet<String>>>,
+}
+
+impl Default for Channel {
+	fn default() -> Self {
+		// Ids start at the current time, so ids from before a restart still compare lower.
+		let next_id = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX))
+			.unwrap_or_default();
+
+		Channel {
+			sender: broadcast::channel(HISTORY).0,
+			recent: VecDeque::with_capacity(HISTORY),
+			next_id,
+			latest: None,
+		}
+	}
+}
+
+/// Live event streams, by flow name.
+#[derive(Debug, Default)]
+pub struct Streams(Mutex<HashMap<String, Channel>>);
+
+fn entries(payload: Option<&prost_types::Any>) -> Vec<Entry> {
+	payload
+		.cloned()
+		.and_then(|payload| Feed::try_from(payload).ok())
+		.map(|feed| feed.entries)
+		.unwrap_or_default()
+}
+
+fn entry_ids(outputs: &Outputs) -> HashMap<String, HashSet<String>> {
+	outputs
+		.iter()
+		.map(|(output, payload)| {
+			let ids = entries(payload.as_ref())
+				.into_iter()
+				.map(|e| e.id)
+				.collect();
+			(output.clone(), ids)
+		})
+		.collect()
+}
+
+impl Streams {
+	/// Publishes the entries in `outputs` that the flow's previous run didn't produce, returning
+	/// how many there were.
+	///
+	/// The previous run is the last one published here, or `previous` (the latest snapshot) after
+	/// a restart. Without either, the run only seeds the stream, as every entry would look new.
+	pub fn publish(&self, flow: &str, previous: Option<&Outputs>, outputs: &Outputs) -> usize {
+		let mut streams = self.0.lock().unwrap();
+		let channel = streams.entry(flow.to_string()).or_default();
+
+		let latest = channel
+			.latest
+			.replace(entry_ids(outputs))
+			.or_else(|| previous.map(entry_ids));
+		let Some(latest) = latest else {
+			return 0;
+		};
+		let mut published = 0;
+
+		for (output, payload) in outputs {
+			let seen = latest.get(output);
+			for entry in entries(payload.as_ref()) {
+				if seen.is_some_and(|seen| seen.contains(&entry.id)) {
+					continue;
+				}
+
+				let event = Arc::new(FlowEvent {
+					id: channel.next_id,
+					output: output.clone(),
+					entry: entry.into(),
+				});
+				channel.next_id += 1;
+
+				if channel.recent.len() == HISTORY {
+					channel.recent.pop_front();
+				}
+				channel.recent.push_back(event.clone());
+				// Fails only without subscribers.
+				let _ = channel.sender.send(event);
+				published += 1;
+			}
+		}
+
+		published
+	}
+
+	/// Events of the flow `name` after `last_event_id`, followed by live ones.
+	///
+	/// The stream ends if the client falls behind, so it can resume from its last event.
+	pub fn subscribe(
+		&self,
+		flow: &str,
+		last_event_id: Option<u64>,
+	) -> impl Stream<Item = Arc<FlowEvent>> + use<> {
+		let mut streams = self.0.lock().unwrap();
+		let channel = streams.entry(flow.to_string()).or_default();
+
+		let replay: Vec<Arc<FlowEvent>> = match last_event_id {
+			Some(last) => channel
+				.recent
+				.iter()
+				.filter(|e| e.id > last)
+				.cloned()
+				.collect(),
+			None => Vec::new(),
+		};
+		let receiver = channel.sender.subscribe();
+
+		let live = stream::unfold(receiver, |mut receiver| async move {
+			let event = receiver.recv().await.ok()?;
+			Some((event, receiver))
+		});
+		stream::iter(replay).chain(live)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::pin::pin;
+
+	use futures::StreamExt;
+	use rssflow_service::proto::feed::{Entry, Feed};
+
+	use super::Streams;
+	use crate::flow::Outputs;
+
+	fn outputs(ids: &[&str]) -> Outputs {
+		let feed = Feed {
+			entries: ids
+				.iter()
+				.map(|id| Entry {
+					id: (*id).to_string(),
+					..Entry::default()
+				})
+				.collect(),
+			..Feed::default()
+		};
+		Outputs::from([("out".to_string(), Some(feed.into()))])
+	}
+
+	#[test]
+	fn seeds_without_previous_run() {
+		let streams = Streams::default();
+
+		assert_eq!(streams.publish("flow", None, &outputs(&["a", "b"])), 0);
+		assert_eq!(streams.publish("flow", None, &outputs(&["a", "b"])), 0);
+		assert_eq!(streams.publish("flow", None, &outputs(&["b", "c"])), 1);
+	}
+
+	#[test]
+	fn diffs_against_snapshot_after_restart() {
+		let streams = Streams::default();
+		let snapshot = outputs(&["a"]);
+
+		assert_eq!(
+			streams.publish("flow", Some(&snapshot), &outputs(&["a", "b"])),
+			1
+		);
+		// The stream's own latest run takes precedence over the snapshot.
+		assert_eq!(
+			streams.publish("flow", Some(&snapshot), &outputs(&["a", "b"])),
+			0
+		);
+	}
+
+	#[tokio::test]
+	async fn resumes_after_last_event() {
+		let streams = Streams::default();
+		streams.publish("flow", None, &outputs(&[]));
+		streams.publish("flow", None, &outputs(&["a", "b"]));
+
+		let mut stream = pin!(streams.subscribe("flow", Some(0)));
+		let first = stream.next().await.unwrap();
+		assert_eq!(first.entry.id, "a");
+
+		let mut resumed = pin!(streams.subscribe("flow", Some(first.id)));
+		assert_eq!(resumed.next().await.unwrap().entry.id, "b");
+	}
+}